@@ -0,0 +1,118 @@
+use crate::{interval::Interval, ray::Ray, vec3::Vec3};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Self::new(Interval::EMPTY, Interval::EMPTY, Interval::EMPTY);
+    pub const UNIVERSE: Self =
+        Self::new(Interval::UNIVERSE, Interval::UNIVERSE, Interval::UNIVERSE);
+
+    pub const fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Box spanning the two corners `a` and `b`, in any order.
+    pub fn from_points(a: Vec3, b: Vec3) -> Self {
+        Self::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+        .pad()
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self::new(
+            Interval::hull(self.x, other.x),
+            Interval::hull(self.y, other.y),
+            Interval::hull(self.z, other.z),
+        )
+    }
+
+    pub fn axis(&self, n: usize) -> Interval {
+        match n {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    pub fn min(&self) -> Vec3 {
+        Vec3::new(self.x.min, self.y.min, self.z.min)
+    }
+
+    pub fn max(&self) -> Vec3 {
+        Vec3::new(self.x.max, self.y.max, self.z.max)
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min() + self.max())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.size() < 0.0 || self.y.size() < 0.0 || self.z.size() < 0.0
+    }
+
+    /// Whether the box has finite extents, as everything but infinite
+    /// primitives does.
+    pub fn is_bounded(&self) -> bool {
+        [self.x, self.y, self.z]
+            .iter()
+            .all(|i| i.min.is_finite() && i.max.is_finite())
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+        if x > y && x > z {
+            0
+        } else if y > z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+        2.0 * (x * y + y * z + z * x)
+    }
+
+    /// Slab test against a ray whose reciprocal direction has been precomputed.
+    pub fn hit(&self, ray: &Ray, inv_dir: Vec3, ray_t: Interval) -> bool {
+        self.clip(ray, inv_dir, ray_t).is_some()
+    }
+
+    /// Part of `ray_t` during which the ray is inside the box.
+    pub fn clip(&self, ray: &Ray, inv_dir: Vec3, ray_t: Interval) -> Option<Interval> {
+        let mut t_min = ray_t.min;
+        let mut t_max = ray_t.max;
+        for n in 0..3 {
+            let axis = self.axis(n);
+            let t0 = (axis.min - ray.origin[n]) * inv_dir[n];
+            let t1 = (axis.max - ray.origin[n]) * inv_dir[n];
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(Interval::new(t_min, t_max))
+    }
+
+    /// Gives flat boxes a little thickness so that slab tests don't miss them.
+    fn pad(self) -> Self {
+        const DELTA: f64 = 1e-4;
+        let pad = |i: Interval| if i.size() < DELTA { i.expand(DELTA) } else { i };
+        Self::new(pad(self.x), pad(self.y), pad(self.z))
+    }
+}
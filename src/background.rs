@@ -0,0 +1,35 @@
+use crate::vec3::Vec3;
+
+/// Radiance arriving along rays that escape the scene.
+#[derive(Debug, Clone, Copy)]
+pub enum Background {
+    /// Blend from `bottom` when looking straight down to `top` when looking
+    /// straight up.
+    Gradient {
+        bottom: Vec3,
+        top: Vec3,
+    },
+    Solid(Vec3),
+}
+
+impl Background {
+    pub fn color(&self, direction: Vec3) -> Vec3 {
+        match *self {
+            Self::Gradient { bottom, top } => {
+                let a = 0.5 * (direction.unit().y + 1.0);
+                (1.0 - a) * bottom + a * top
+            }
+            Self::Solid(color) => color,
+        }
+    }
+}
+
+impl Default for Background {
+    /// A light blue sky.
+    fn default() -> Self {
+        Self::Gradient {
+            bottom: Vec3::splat(1.0),
+            top: Vec3::new(0.5, 0.7, 1.0),
+        }
+    }
+}
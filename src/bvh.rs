@@ -0,0 +1,383 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{HitRecord, HitTarget},
+    interval::Interval,
+    light::Light,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+const BUCKET_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+/// Cost of visiting an interior node relative to intersecting one object.
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Bounding volume hierarchy over a set of objects, built with the surface
+/// area heuristic and flattened into a depth-first array of nodes. Objects
+/// without a finite bounding box, like planes, are kept aside and tested
+/// against every ray.
+pub struct Bvh<T = Box<dyn HitTarget>> {
+    nodes: Vec<Node>,
+    objects: Vec<T>,
+    unbounded: Vec<T>,
+}
+
+struct Node {
+    bbox: Aabb,
+    /// First object for leaves, index of the second child for interior nodes.
+    /// The first child of an interior node always directly follows it.
+    offset: usize,
+    /// Number of objects in a leaf, zero for interior nodes.
+    count: usize,
+    /// Split axis of an interior node.
+    axis: usize,
+}
+
+struct BuildItem {
+    index: usize,
+    bbox: Aabb,
+    centroid: Vec3,
+}
+
+#[derive(Clone, Copy)]
+struct Bucket {
+    count: usize,
+    bbox: Aabb,
+}
+
+impl<T: HitTarget> Bvh<T> {
+    pub fn new(objects: Vec<T>) -> Self {
+        let (objects, unbounded) = objects
+            .into_iter()
+            .partition::<Vec<_>, _>(|object| object.bounding_box().is_bounded());
+
+        let mut items = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bbox = object.bounding_box();
+                BuildItem {
+                    index,
+                    bbox,
+                    centroid: bbox.centroid(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut nodes = Vec::with_capacity(2 * items.len());
+        if !items.is_empty() {
+            build(&mut nodes, &mut items, 0);
+        }
+
+        let mut slots = objects.into_iter().map(Some).collect::<Vec<_>>();
+        let objects = items
+            .iter()
+            .map(|item| slots[item.index].take().unwrap())
+            .collect();
+
+        Self {
+            nodes,
+            objects,
+            unbounded,
+        }
+    }
+}
+
+fn build(nodes: &mut Vec<Node>, items: &mut [BuildItem], offset: usize) -> usize {
+    let bbox = items
+        .iter()
+        .fold(Aabb::EMPTY, |bbox, item| bbox.union(item.bbox));
+    let index = nodes.len();
+    nodes.push(Node {
+        bbox,
+        offset,
+        count: items.len(),
+        axis: 0,
+    });
+
+    if items.len() == 1 {
+        return index;
+    }
+
+    let Some((axis, mid)) = split(items, &bbox) else {
+        return index;
+    };
+
+    build(nodes, &mut items[..mid], offset);
+    let second = build(nodes, &mut items[mid..], offset + mid);
+    let node = &mut nodes[index];
+    node.offset = second;
+    node.count = 0;
+    node.axis = axis;
+    index
+}
+
+/// Partitions `items` along the cheapest SAH split, returning the split axis
+/// and the partition point, or `None` if the items are better left in a leaf.
+fn split(items: &mut [BuildItem], bbox: &Aabb) -> Option<(usize, usize)> {
+    let centroid_bounds = items.iter().fold(Aabb::EMPTY, |bbox, item| {
+        bbox.union(Aabb::new(
+            Interval::new(item.centroid.x, item.centroid.x),
+            Interval::new(item.centroid.y, item.centroid.y),
+            Interval::new(item.centroid.z, item.centroid.z),
+        ))
+    });
+    let axis = centroid_bounds.longest_axis();
+    let extent = centroid_bounds.axis(axis);
+    if extent.size() <= 0.0 {
+        // Every centroid coincides, so no split can separate them.
+        return None;
+    }
+
+    let bucket_of = |item: &BuildItem| {
+        let b = (BUCKET_COUNT as f64 * (item.centroid[axis] - extent.min) / extent.size()) as usize;
+        b.min(BUCKET_COUNT - 1)
+    };
+
+    let mut buckets = [Bucket {
+        count: 0,
+        bbox: Aabb::EMPTY,
+    }; BUCKET_COUNT];
+    for item in items.iter() {
+        let bucket = &mut buckets[bucket_of(item)];
+        bucket.count += 1;
+        bucket.bbox = bucket.bbox.union(item.bbox);
+    }
+
+    // costs[i] is the cost of splitting after bucket i.
+    let mut costs = [0.0; BUCKET_COUNT - 1];
+    let mut below = Bucket {
+        count: 0,
+        bbox: Aabb::EMPTY,
+    };
+    for (cost, bucket) in costs.iter_mut().zip(&buckets) {
+        below.count += bucket.count;
+        below.bbox = below.bbox.union(bucket.bbox);
+        *cost = below.count as f64 * below.bbox.surface_area();
+    }
+    let mut above = Bucket {
+        count: 0,
+        bbox: Aabb::EMPTY,
+    };
+    for (cost, bucket) in costs.iter_mut().zip(&buckets[1..]).rev() {
+        above.count += bucket.count;
+        above.bbox = above.bbox.union(bucket.bbox);
+        *cost += above.count as f64 * above.bbox.surface_area();
+    }
+
+    let (best, min_cost) = costs
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, &cost)| (i, TRAVERSAL_COST + cost / bbox.surface_area()))
+        .unwrap();
+
+    let leaf_cost = items.len() as f64;
+    if items.len() <= MAX_LEAF_SIZE && min_cost >= leaf_cost {
+        return None;
+    }
+
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if bucket_of(&items[i]) <= best {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    if mid == 0 || mid == items.len() {
+        mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    }
+
+    Some((axis, mid))
+}
+
+impl<T: HitTarget> Bvh<T> {
+    /// Calls `visit` with every object whose box the ray passes through
+    /// within `ray_t`, nearest boxes first. `visit` returns the distance of
+    /// any hit it finds, which then culls everything further away.
+    fn traverse(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        mut visit: impl FnMut(&T, Interval) -> Option<f64>,
+    ) {
+        let mut closest_so_far = ray_t.max;
+        for object in &self.unbounded {
+            if let Some(t) = visit(object, ray_t.with_max(closest_so_far)) {
+                closest_so_far = t;
+            }
+        }
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let d = ray.direction;
+        let inv_dir = Vec3::new(1.0 / d.x, 1.0 / d.y, 1.0 / d.z);
+        let dir_is_neg = [d.x < 0.0, d.y < 0.0, d.z < 0.0];
+
+        // SAH splits can peel objects off one at a time, so there's no bound
+        // on the depth of the tree.
+        let mut stack = Vec::with_capacity(64);
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node.bbox.hit(ray, inv_dir, ray_t.with_max(closest_so_far)) {
+                if node.count > 0 {
+                    for object in &self.objects[node.offset..node.offset + node.count] {
+                        if let Some(t) = visit(object, ray_t.with_max(closest_so_far)) {
+                            closest_so_far = t;
+                        }
+                    }
+                } else {
+                    // Visit the near child first so that the far one can be
+                    // culled by the closer hit.
+                    let (near, far) = if dir_is_neg[node.axis] {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack.push(far);
+                    current = near;
+                    continue;
+                }
+            }
+
+            match stack.pop() {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+    }
+
+    fn closest(
+        &self,
+        ray: &Ray,
+        ray_t: Interval,
+        hit: impl Fn(&T, Interval) -> Option<HitRecord>,
+    ) -> Option<HitRecord> {
+        let mut last_hit = None;
+        self.traverse(ray, ray_t, |object, ray_t| {
+            let hit = hit(object, ray_t)?;
+            let t = hit.t;
+            last_hit = Some(hit);
+            Some(t)
+        });
+        last_hit
+    }
+}
+
+impl<T: HitTarget> HitTarget for Bvh<T> {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.closest(ray, ray_t, |object, ray_t| object.hit(ray, ray_t))
+    }
+
+    fn hit_surface(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.closest(ray, ray_t, |object, ray_t| object.hit_surface(ray, ray_t))
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f64 {
+        let mut transmittance = 1.0;
+        self.traverse(ray, ray_t, |object, ray_t| {
+            transmittance *= object.transmittance(ray, ray_t);
+            None
+        });
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        if !self.unbounded.is_empty() {
+            return Aabb::UNIVERSE;
+        }
+        self.nodes.first().map_or(Aabb::EMPTY, |node| node.bbox)
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        for object in self.objects.iter().chain(&self.unbounded) {
+            object.lights(lights);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use crate::{hit::HitWorld, material::LambertianMaterial, plane::Plane, sphere::Sphere};
+
+    #[test]
+    fn matches_linear_search() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let mut world = HitWorld::new();
+        let mut objects: Vec<Box<dyn HitTarget>> = Vec::new();
+        let ground = Vec3::new(0.0, -5.0, 0.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        world.push(Plane::new(ground, up, material.clone()));
+        objects.push(Box::new(Plane::new(ground, up, material.clone())));
+        for _ in 0..200 {
+            let center = Vec3::new(
+                rng.random_range(-10.0..10.0),
+                rng.random_range(-10.0..10.0),
+                rng.random_range(-10.0..10.0),
+            );
+            let radius = rng.random_range(0.1..1.0);
+            world.push(Sphere::new(center, radius, material.clone()));
+            objects.push(Box::new(Sphere::new(center, radius, material.clone())));
+        }
+        let bvh = Bvh::new(objects);
+
+        for _ in 0..1000 {
+            let origin = Vec3::new(
+                rng.random_range(-15.0..15.0),
+                rng.random_range(-15.0..15.0),
+                rng.random_range(-15.0..15.0),
+            );
+            let direction = Vec3::new(
+                rng.random_range(-1.0..1.0),
+                rng.random_range(-1.0..1.0),
+                rng.random_range(-1.0..1.0),
+            );
+            let ray = Ray::new(origin, direction);
+            let ray_t = Interval::new(0.001, f64::INFINITY);
+            let expected = world.hit(&ray, ray_t).map(|hit| hit.t);
+            let actual = bvh.hit(&ray, ray_t).map(|hit| hit.t);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn traverses_deep_trees() {
+        // Each split peels off the farthest of these exponentially spaced
+        // spheres, giving a chain as deep as there are spheres.
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let objects = (0..100)
+            .map(|i| {
+                let center = Vec3::new(16f64.powi(i), 0.0, 0.0);
+                Box::new(Sphere::new(center, 0.25, material.clone())) as Box<dyn HitTarget>
+            })
+            .collect();
+        let bvh = Bvh::new(objects);
+        fn depth(nodes: &[Node], index: usize) -> usize {
+            let node = &nodes[index];
+            if node.count > 0 {
+                1
+            } else {
+                1 + depth(nodes, index + 1).max(depth(nodes, node.offset))
+            }
+        }
+        assert!(depth(&bvh.nodes, 0) > 64);
+
+        // Every level leaves its far child on the stack on the way down to
+        // the nearest sphere.
+        let ray = Ray::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = bvh.hit(&ray, Interval::new(0.001, f64::INFINITY)).unwrap();
+        assert!((hit.t - 1.75).abs() < 1e-9);
+        let ray = Ray::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(bvh.hit(&ray, Interval::new(0.001, f64::INFINITY)).is_none());
+    }
+}
@@ -1,16 +1,29 @@
-use std::{sync::mpsc, time::Instant};
+use std::{
+    collections::BTreeMap,
+    iter,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 
-use rand::Rng;
 use rayon::prelude::*;
 
-use crate::{color::Color, hit::HitTarget, interval::Interval, ray::Ray, vec3::Vec3};
+use crate::{
+    checkpoint::Checkpoint,
+    color::luminance,
+    film::{Film, Welford},
+    filter::{Filter, FilterKind},
+    integrator::{Integrator, Tracer},
+    ray::{Cone, Ray},
+    sampler::{Sampler, SamplerKind},
+    vec3::Vec3,
+};
 
 pub struct Camera {
     image_width: u32,
     image_height: u32,
-    pixel_samples_scale: f64,
     samples_per_pixel: u16,
-    max_depth: u32,
+    min_samples: u16,
+    adaptive_threshold: Option<f64>,
     center: Vec3,
     pixel00_loc: Vec3,
     pixel_delta_u: Vec3,
@@ -18,28 +31,173 @@ pub struct Camera {
     defocus_disk_u: Vec3,
     defocus_disk_v: Vec3,
     defocus_angle: f64,
+    /// Angle subtended by a pixel, for texture filtering.
+    pixel_spread: f64,
+    shutter_open: f64,
+    shutter_close: f64,
+    sampler: SamplerKind,
+    seed: u64,
+    /// Samples each pixel's sampler spreads its values over, which is
+    /// `samples_per_pixel` unless carrying on an earlier render.
+    sequence: u32,
+    filter_kind: FilterKind,
+    filter: Box<dyn Filter>,
+}
+
+/// Everything needed to set up a [`Camera`].
+#[derive(Debug, Clone)]
+pub struct CameraSettings {
+    pub image_width: u32,
+    pub aspect_ratio: f64,
+    /// Image height in pixels, or `None` to derive it from the width and
+    /// aspect ratio.
+    pub image_height: Option<u32>,
+    /// Samples per pixel, or the most any pixel takes with adaptive sampling.
+    pub samples_per_pixel: u16,
+    /// Fewest samples a pixel takes with adaptive sampling, capped at
+    /// `samples_per_pixel`. Pixels take at least two samples where allowed,
+    /// to estimate their noise from.
+    pub min_samples: u16,
+    /// Noise level below which pixels stop taking samples, or `None` to take
+    /// `samples_per_pixel` everywhere. Noise is the standard error of the
+    /// pixel's luminance after gamma encoding, where 1 is full white.
+    pub adaptive_threshold: Option<f64>,
+    /// Vertical field of view in degrees.
+    pub vfov: f64,
+    pub lookfrom: Vec3,
+    pub lookat: Vec3,
+    pub vup: Vec3,
+    /// Variation angle of rays through each pixel, in degrees.
+    pub defocus_angle: f64,
+    /// Distance from `lookfrom` to the plane of perfect focus.
+    pub focus_dist: f64,
+    /// Times between which rays are spread for motion blur.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    /// Seed for the random numbers of every sample. The same seed renders
+    /// the same image.
+    pub seed: u64,
+    /// How the random numbers of the samples of each pixel are spread.
+    pub sampler: SamplerKind,
+    /// How samples are weighted into the pixels around them.
+    pub filter: FilterKind,
+    /// Reach of the filter in pixels, or `None` for the filter's default.
+    pub filter_radius: Option<f64>,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            image_width: 400,
+            aspect_ratio: 16.0 / 9.0,
+            image_height: None,
+            samples_per_pixel: 100,
+            min_samples: 16,
+            adaptive_threshold: None,
+            vfov: 90.0,
+            lookfrom: Vec3::new(0.0, 0.0, 0.0),
+            lookat: Vec3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            seed: 0,
+            sampler: SamplerKind::default(),
+            filter: FilterKind::default(),
+            filter_radius: None,
+        }
+    }
+}
+
+impl CameraSettings {
+    /// Image height in pixels, as set or else derived from the width and
+    /// aspect ratio.
+    pub fn image_height(&self) -> u32 {
+        self.image_height
+            .unwrap_or((self.image_width as f64 / self.aspect_ratio) as u32)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.image_width == 0 {
+            return Err("image width must be at least 1 pixel".to_string());
+        }
+        if !(self.aspect_ratio.is_finite() && self.aspect_ratio > 0.0) {
+            return Err(format!(
+                "aspect ratio must be positive, got {}",
+                self.aspect_ratio
+            ));
+        }
+        if self.image_height == Some(0) {
+            return Err("image height must be at least 1 pixel".to_string());
+        }
+        if self.image_height() == 0 {
+            return Err(format!(
+                "image height would be 0 pixels at width {} and aspect ratio {}",
+                self.image_width, self.aspect_ratio
+            ));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err("samples per pixel must be at least 1".to_string());
+        }
+        if self.min_samples == 0 {
+            return Err("minimum samples per pixel must be at least 1".to_string());
+        }
+        if let Some(threshold) = self.adaptive_threshold
+            && !(threshold.is_finite() && threshold > 0.0)
+        {
+            return Err(format!(
+                "adaptive sampling threshold must be positive, got {threshold}"
+            ));
+        }
+        if let Some(radius) = self.filter_radius
+            && !(radius.is_finite() && radius > 0.0)
+        {
+            return Err(format!("filter radius must be positive, got {radius}"));
+        }
+        if !(self.shutter_open.is_finite() && self.shutter_close.is_finite()) {
+            return Err("shutter times must be finite".to_string());
+        }
+        if self.shutter_close < self.shutter_open {
+            return Err(format!(
+                "shutter closes at {} before it opens at {}",
+                self.shutter_close, self.shutter_open
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Camera {
-    pub fn new(
-        image_width: u32,
-        aspect_ratio: f64,
-        samples_per_pixel: u16,
-        max_depth: u32,
-        vfov: f64,
-        lookfrom: Vec3,
-        lookat: Vec3,
-        vup: Vec3,
-        defocus_angle: f64,
-        focus_dist: f64,
-    ) -> Self {
-        let image_height = image_width as f64 / aspect_ratio;
-        let image_height = if image_height >= 1.0 {
-            image_height as u32
-        } else {
-            1
-        };
-        let pixel_samples_scale = 1.0 / samples_per_pixel as f64;
+    /// # Panics
+    ///
+    /// If the settings don't pass [`CameraSettings::validate`].
+    pub fn new(settings: &CameraSettings) -> Self {
+        if let Err(e) = settings.validate() {
+            panic!("invalid camera settings: {e}");
+        }
+
+        let CameraSettings {
+            image_width,
+            aspect_ratio: _,
+            image_height: _,
+            samples_per_pixel,
+            min_samples,
+            adaptive_threshold,
+            vfov,
+            lookfrom,
+            lookat,
+            vup,
+            defocus_angle,
+            focus_dist,
+            shutter_open,
+            shutter_close,
+            seed,
+            sampler,
+            filter,
+            filter_radius,
+        } = *settings;
+        let image_height = settings.image_height();
         let theta = vfov.to_radians();
         let h = (theta / 2.0).tan();
         let viewport_height = 2.0 * h * focus_dist;
@@ -66,9 +224,9 @@ impl Camera {
         Self {
             image_width,
             image_height,
-            pixel_samples_scale,
             samples_per_pixel,
-            max_depth,
+            min_samples: min_samples.min(samples_per_pixel),
+            adaptive_threshold,
             center,
             pixel00_loc,
             pixel_delta_u,
@@ -76,95 +234,384 @@ impl Camera {
             defocus_disk_u,
             defocus_disk_v,
             defocus_angle,
+            pixel_spread: pixel_delta_u.len() / focus_dist,
+            shutter_open,
+            shutter_close,
+            sampler,
+            seed,
+            sequence: samples_per_pixel as u32,
+            filter_kind: filter,
+            filter: filter.build(filter_radius.unwrap_or(filter.default_radius())),
         }
     }
 
-    pub fn render(&self, target: &dyn HitTarget) {
-        let (tx, rx) = mpsc::channel();
-        let mut out = vec![vec![]; self.image_height as usize];
+    /// Renders the scene seen by `tracer`, using `integrator` to find the
+    /// light arriving along each camera ray.
+    pub fn render(&self, integrator: &dyn Integrator, tracer: &Tracer) -> Film {
         let start = Instant::now();
+        let film = Film::new(self.image_width, self.image_height);
+        let film = self.render_pass(
+            integrator,
+            tracer,
+            &film,
+            self.samples_per_pixel,
+            &mut |_| {},
+        );
+        eprintln!("Done in {:?}", start.elapsed());
+        film
+    }
+
+    /// Renders like [`Camera::render`], but adding samples to `film` in
+    /// passes that double the samples per pixel until reaching
+    /// `samples_per_pixel`. The film may hold an earlier render, in which
+    /// case passes it has already done are skipped. The film so far goes to
+    /// `update` after each pass or, given an `interval`, whenever that much
+    /// time has passed since the last update.
+    ///
+    /// # Panics
+    ///
+    /// If `film` isn't the size of the image.
+    pub fn render_progressive(
+        &self,
+        integrator: &dyn Integrator,
+        tracer: &Tracer,
+        mut film: Film,
+        interval: Option<Duration>,
+        update: &mut dyn FnMut(&Film),
+    ) -> Film {
+        assert!(
+            film.width() == self.image_width && film.rows() == (0..self.image_height),
+            "film doesn't match the size of the image"
+        );
+        let start = Instant::now();
+        let mut last_update = Instant::now();
+        let spp = self.samples_per_pixel;
+        let done = film.fewest_samples();
+        let passes = iter::successors(Some(1), |&n| {
+            (n < spp).then(|| n.saturating_mul(2).min(spp))
+        })
+        .filter(|&n| n as u32 > done);
+        for samples in passes {
+            let pass = self.render_pass(integrator, tracer, &film, samples, &mut |pass| {
+                if interval.is_some_and(|interval| last_update.elapsed() >= interval) {
+                    let mut snapshot = film.clone();
+                    snapshot.merge(pass);
+                    update(&snapshot);
+                    last_update = Instant::now();
+                }
+            });
+            film.merge(&pass);
+            eprintln!("Pass done: {samples} samples per pixel");
+            if interval.is_none_or(|interval| last_update.elapsed() >= interval) {
+                update(&film);
+                last_update = Instant::now();
+            }
+        }
+        eprintln!("Done in {:?}", start.elapsed());
+        film
+    }
+
+    /// Checkpoint of `film`, rendered by this camera, to carry on later with
+    /// [`Camera::resume`].
+    pub fn checkpoint(&self, film: &Film) -> Checkpoint {
+        Checkpoint {
+            seed: self.seed,
+            sampler: self.sampler,
+            sequence: self.sequence,
+            filter: self.filter_kind,
+            filter_radius: self.filter.radius(),
+            film: film.clone(),
+        }
+    }
+
+    /// Takes on the samplers and filter of the render saved in
+    /// `checkpoint`, and returns its film for
+    /// [`Camera::render_progressive`] to add samples to.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> Result<Film, String> {
+        let film = checkpoint.film;
+        if film.width() != self.image_width || film.rows() != (0..self.image_height) {
+            return Err(format!(
+                "checkpoint is {}x{} pixels, but the image is {}x{}",
+                film.width(),
+                film.rows().len(),
+                self.image_width,
+                self.image_height
+            ));
+        }
+        self.seed = checkpoint.seed;
+        self.sampler = checkpoint.sampler;
+        self.sequence = checkpoint.sequence;
+        self.filter_kind = checkpoint.filter;
+        self.filter = checkpoint.filter.build(checkpoint.filter_radius);
+        Ok(film)
+    }
+
+    /// Takes samples in every pixel of `film` until it has `samples` or has
+    /// converged, and returns the new samples as a film of their own. The
+    /// new samples so far go to `progress` as each row finishes.
+    fn render_pass(
+        &self,
+        integrator: &dyn Integrator,
+        tracer: &Tracer,
+        film: &Film,
+        samples: u16,
+        progress: &mut dyn FnMut(&Film),
+    ) -> Film {
+        let (width, height) = (self.image_width, self.image_height);
+        let filter = self.filter.as_ref();
+        let (tx, rx) = mpsc::channel();
+        let mut pass = Film::new(width, height);
         std::thread::scope(|scope| {
             scope.spawn(|| {
-                (0..self.image_height).par_bridge().for_each(|j| {
-                    let mut line = Vec::new();
-                    for i in 0..self.image_width {
-                        let mut pixel_color = Vec3::splat(0.0);
-                        for _ in 0..self.samples_per_pixel {
-                            let ray = self.get_ray(i, j);
-                            pixel_color =
-                                pixel_color + self.ray_color(&ray, self.max_depth, target);
+                (0..height).par_bridge().for_each(|j| {
+                    let mut band = Film::band(width, height, j, filter);
+                    let mut sampler = self.sampler.build(self.seed, self.sequence);
+                    for i in 0..width {
+                        let mut stats = film.stats(i, j);
+                        let mut added = Welford::default();
+                        while stats.count() < samples as u32 && !self.converged(&stats) {
+                            sampler.start((i, j), stats.count());
+                            let (u, v) = sampler.get_2d();
+                            let position = (i as f64 + u, j as f64 + v);
+                            let ray = self.get_ray(position, sampler.as_mut());
+                            let radiance = integrator.radiance(tracer, &ray, sampler.as_mut());
+                            band.add_sample(filter, position, radiance);
+                            stats.add(luminance(radiance));
+                            added.add(luminance(radiance));
                         }
-                        let color = Color::from(pixel_color * self.pixel_samples_scale);
-                        line.push(color.to_int());
+                        band.add_stats(i, j, added);
                     }
-                    tx.send((j, line)).unwrap();
+                    tx.send((j, band)).unwrap();
                 });
                 drop(tx);
             });
 
+            // Bands overlap, so they're merged in order of rows to add up
+            // the same way whichever thread finishes first.
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
             let mut done = 0;
-            for (j, line) in rx {
-                out[j as usize] = line;
+            for (j, band) in rx {
+                pending.insert(j, band);
+                while let Some(band) = pending.remove(&next) {
+                    pass.merge(&band);
+                    next += 1;
+                }
                 done += 1;
-                eprintln!("Scanline done: {done} of {}", self.image_height);
+                eprintln!("Scanline done: {done} of {height}");
+                progress(&pass);
             }
         });
-
-        println!("P3");
-        println!("{} {}", self.image_width, self.image_height);
-        println!("255");
-        for line in out {
-            for color in line {
-                println!("{}", color);
-            }
-        }
-
-        eprintln!("Done in {:?}", start.elapsed());
+        pass
     }
 
-    fn ray_color(&self, ray: &Ray, depth: u32, target: &dyn HitTarget) -> Vec3 {
-        if depth == 0 {
-            return Vec3::splat(0.0);
-        }
-
-        if let Some(hit) = target.hit(ray, Interval::new(0.001, f64::INFINITY)) {
-            return if let Some(scatter) = hit.material.scatter(ray, &hit) {
-                scatter.attenuation * self.ray_color(&scatter.scattered, depth - 1, target)
-            } else {
-                Vec3::splat(0.0)
-            };
+    /// Whether a pixel with sample statistics `stats` can stop taking
+    /// samples before reaching `samples_per_pixel`.
+    fn converged(&self, stats: &Welford) -> bool {
+        let Some(threshold) = self.adaptive_threshold else {
+            return false;
+        };
+        // A single sample says nothing about the variance.
+        if stats.count() < (self.min_samples as u32).max(2) {
+            return false;
         }
-
-        let unit_direction = ray.direction.unit();
-        let a = 0.5 * (unit_direction.y + 1.0);
-        (1.0 - a) * Vec3::splat(1.0) + a * Vec3::new(0.5, 0.7, 1.0)
+        // Gamma encoding takes the square root, which scales errors around
+        // the mean by 1 / (2 sqrt(mean)). The floor keeps black pixels from
+        // dividing by zero.
+        let mean = stats.mean().max(1e-6);
+        stats.standard_error() / (2.0 * mean.sqrt()) < threshold
     }
 
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        let offset = self.sample_square();
-        let pixel_sample = self.pixel00_loc
-            + (i as f64 + offset.x) * self.pixel_delta_u
-            + (j as f64 + offset.y) * self.pixel_delta_v;
+    /// Ray through film position (`x`, `y`), in pixels from the top left
+    /// corner of the image.
+    fn get_ray(&self, (x, y): (f64, f64), sampler: &mut dyn Sampler) -> Ray {
+        let pixel_sample =
+            self.pixel00_loc + (x - 0.5) * self.pixel_delta_u + (y - 0.5) * self.pixel_delta_v;
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(sampler)
         };
         let ray_direction = pixel_sample - ray_origin;
         Ray::new(ray_origin, ray_direction)
+            .with_cone(Cone {
+                width: 0.0,
+                spread: self.pixel_spread,
+            })
+            .with_time(self.sample_time(sampler))
+            .with_seed(sampler.next_u64())
     }
 
-    fn sample_square(&self) -> Vec3 {
-        let mut rng = rand::rng();
-        Vec3::new(
-            rng.random_range(-0.5..0.5),
-            rng.random_range(-0.5..0.5),
-            0.0,
-        )
+    fn sample_time(&self, sampler: &mut dyn Sampler) -> f64 {
+        let t = sampler.get_1d();
+        self.shutter_open + t * (self.shutter_close - self.shutter_open)
     }
 
-    fn defocus_disk_sample(&self) -> Vec3 {
-        let p = Vec3::random_in_unit_disk();
+    fn defocus_disk_sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let p = Vec3::random_in_unit_disk(sampler);
         self.center + p.x * self.defocus_disk_u + p.y * self.defocus_disk_v
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        background::Background,
+        hit::HitWorld,
+        integrator::PathIntegrator,
+        light::LightList,
+        material::{DielectricMaterial, LambertianMaterial},
+        sphere::Sphere,
+    };
+
+    #[test]
+    fn height_comes_out_as_requested() {
+        for height in [29, 52, 53, 58, 360] {
+            let settings = CameraSettings {
+                image_width: 640,
+                image_height: Some(height),
+                ..Default::default()
+            };
+            assert_eq!(settings.image_height(), height);
+            assert_eq!(Camera::new(&settings).image_height, height);
+        }
+        let settings = CameraSettings {
+            image_height: Some(0),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+    }
+
+    /// Runs `test` with a path tracer over a glass sphere on a diffuse
+    /// ground under a white sky.
+    fn with_scene(test: impl FnOnce(&PathIntegrator, &Tracer)) {
+        let mut world = HitWorld::new();
+        let diffuse = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        world.push(Sphere::new(Vec3::new(0.0, -100.5, -1.0), 100.0, diffuse));
+        let glass = Arc::new(DielectricMaterial::new(1.5));
+        world.push(Sphere::new(Vec3::new(0.0, 0.0, -1.0), 0.5, glass));
+        let lights = LightList::new(&world);
+        let background = Background::Solid(Vec3::splat(1.0));
+        test(
+            &PathIntegrator::new(10, 3),
+            &Tracer::new(&world, &lights, &background),
+        );
+    }
+
+    /// Exact bits of the film's image, for comparing renders.
+    fn bits(film: &Film) -> Vec<u64> {
+        let image = film.image();
+        let pixels = image.pixels().iter();
+        pixels
+            .flat_map(|c| [c.r, c.g, c.b].map(f64::to_bits))
+            .collect()
+    }
+
+    #[test]
+    fn seed_determines_image() {
+        with_scene(|integrator, tracer| {
+            let render = |seed| {
+                let camera = Camera::new(&CameraSettings {
+                    image_width: 16,
+                    samples_per_pixel: 4,
+                    seed,
+                    ..CameraSettings::default()
+                });
+                bits(&camera.render(integrator, tracer))
+            };
+            assert_eq!(render(1), render(1));
+            assert_ne!(render(1), render(2));
+        });
+    }
+
+    #[test]
+    fn progressive_passes_add_up_to_full_render() {
+        with_scene(|integrator, tracer| {
+            let camera = Camera::new(&CameraSettings {
+                image_width: 8,
+                samples_per_pixel: 5,
+                ..CameraSettings::default()
+            });
+
+            let mut updates = Vec::new();
+            let film = Film::new(8, 4);
+            let film = camera.render_progressive(integrator, tracer, film, None, &mut |film| {
+                updates.push(film.stats(0, 0).count())
+            });
+            assert_eq!(updates, [1, 2, 4, 5]);
+            let full = camera.render(integrator, tracer).image();
+            for (a, b) in film.image().pixels().iter().zip(full.pixels()) {
+                assert!((a.r - b.r).abs() < 1e-9 && (a.g - b.g).abs() < 1e-9);
+            }
+        });
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_flat_pixels_early() {
+        with_scene(|integrator, tracer| {
+            let camera = Camera::new(&CameraSettings {
+                image_width: 16,
+                samples_per_pixel: 64,
+                min_samples: 1,
+                adaptive_threshold: Some(0.01),
+                ..CameraSettings::default()
+            });
+
+            let film = camera.render(integrator, tracer);
+            // The top corner sees only the sky, which every sample agrees on.
+            assert_eq!(film.stats(0, 0).count(), 2);
+            // Where the glass sphere refracts a mix of ground and sky, pixels
+            // stay noisy and take every sample.
+            let counts = (0..9).flat_map(|j| (0..16).map(move |i| (i, j)));
+            let counts = counts
+                .map(|(i, j)| film.stats(i, j).count())
+                .collect::<Vec<_>>();
+            assert_eq!(counts.iter().max(), Some(&64));
+            assert!(counts.iter().filter(|&&n| n == 64).count() > 4);
+        });
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_one() {
+        with_scene(|integrator, tracer| {
+            let settings = |sampler, samples_per_pixel, seed| CameraSettings {
+                image_width: 8,
+                samples_per_pixel,
+                seed,
+                sampler,
+                ..CameraSettings::default()
+            };
+            let render = |camera: &Camera, film| {
+                camera.render_progressive(integrator, tracer, film, None, &mut |_| {})
+            };
+
+            for sampler in [SamplerKind::Sobol, SamplerKind::Stratified] {
+                let first = Camera::new(&settings(sampler, 2, 3));
+                let checkpoint = first.checkpoint(&render(&first, Film::new(8, 4)));
+                let mut resumed = Camera::new(&settings(SamplerKind::Independent, 5, 0));
+                let film = resumed.resume(checkpoint).unwrap();
+                let film = render(&resumed, film);
+                assert_eq!(film.fewest_samples(), 5);
+                // The samplers keep spreading values over the first render's
+                // two samples, as if it had gone on to take five.
+                let mut whole = Camera::new(&settings(sampler, 5, 3));
+                let longer = bits(&render(&whole, Film::new(8, 4)));
+                whole.sequence = 2;
+                assert_eq!(bits(&film), bits(&render(&whole, Film::new(8, 4))));
+                if sampler == SamplerKind::Stratified {
+                    assert_ne!(bits(&film), longer);
+                }
+            }
+
+            let first = Camera::new(&settings(SamplerKind::Sobol, 2, 3));
+            let mut wide = Camera::new(&CameraSettings {
+                image_width: 9,
+                ..settings(SamplerKind::Sobol, 5, 3)
+            });
+            assert!(wide.resume(first.checkpoint(&Film::new(8, 4))).is_err());
+        });
+    }
+}
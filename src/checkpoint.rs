@@ -0,0 +1,169 @@
+//! Saved progress of a render, for carrying it on later.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{film::Film, filter::FilterKind, sampler::SamplerKind};
+
+const MAGIC: &[u8; 16] = b"rt checkpoint 1\n";
+
+/// The film of an unfinished render along with everything that decides the
+/// samples it takes next.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// Seed, kind and sequence length of the samplers. A sample is fixed by
+    /// these, its pixel and its index, so along with the sample counts in
+    /// `film` they make up the whole state of the samplers.
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    pub sequence: u32,
+    /// Filter the samples were weighted into pixels with.
+    pub filter: FilterKind,
+    pub filter_radius: f64,
+    pub film: Film,
+}
+
+impl Checkpoint {
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&self.seed.to_le_bytes())?;
+        let sampler = match self.sampler {
+            SamplerKind::Independent => 0u8,
+            SamplerKind::Stratified => 1,
+            SamplerKind::Halton => 2,
+            SamplerKind::Sobol => 3,
+        };
+        out.write_all(&[sampler])?;
+        out.write_all(&self.sequence.to_le_bytes())?;
+        let filter = match self.filter {
+            FilterKind::Box => 0u8,
+            FilterKind::Tent => 1,
+            FilterKind::Gaussian => 2,
+            FilterKind::Mitchell => 3,
+            FilterKind::Lanczos => 4,
+        };
+        out.write_all(&[filter])?;
+        out.write_all(&self.filter_radius.to_le_bytes())?;
+        self.film.write(out)
+    }
+
+    /// Reads a checkpoint written by [`Checkpoint::write`].
+    pub fn read(input: &mut dyn Read) -> io::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a checkpoint"));
+        }
+        let seed = u64::from_le_bytes(read_bytes(input)?);
+        let sampler = match read_bytes(input)? {
+            [0] => SamplerKind::Independent,
+            [1] => SamplerKind::Stratified,
+            [2] => SamplerKind::Halton,
+            [3] => SamplerKind::Sobol,
+            [n] => return Err(invalid_data(format!("unknown sampler {n}"))),
+        };
+        let sequence = u32::from_le_bytes(read_bytes(input)?);
+        if sequence == 0 {
+            return Err(invalid_data("sampler sequence is empty"));
+        }
+        let filter = match read_bytes(input)? {
+            [0] => FilterKind::Box,
+            [1] => FilterKind::Tent,
+            [2] => FilterKind::Gaussian,
+            [3] => FilterKind::Mitchell,
+            [4] => FilterKind::Lanczos,
+            [n] => return Err(invalid_data(format!("unknown filter {n}"))),
+        };
+        let filter_radius = f64::from_le_bytes(read_bytes(input)?);
+        if !(filter_radius.is_finite() && filter_radius > 0.0) {
+            return Err(invalid_data("filter radius must be positive"));
+        }
+        let film = Film::read(input)?;
+        Ok(Self {
+            seed,
+            sampler,
+            sequence,
+            filter,
+            filter_radius,
+            film,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out)?;
+        out.flush()
+    }
+}
+
+fn read_bytes<const N: usize>(input: &mut dyn Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{filter::TentFilter, vec3::Vec3};
+
+    #[test]
+    fn round_trip() {
+        let filter = TentFilter::new(1.0);
+        let mut film = Film::new(3, 2);
+        film.add_sample(&filter, (1.2, 0.7), Vec3::new(0.1, 2.0, 30.0));
+        let mut stats = film.stats(1, 0);
+        stats.add(0.5);
+        stats.add(1.5);
+        film.add_stats(1, 0, stats);
+        let checkpoint = Checkpoint {
+            seed: 7,
+            sampler: SamplerKind::Stratified,
+            sequence: 64,
+            filter: FilterKind::Tent,
+            filter_radius: 1.0,
+            film,
+        };
+
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        let read = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.seed, 7);
+        assert_eq!(read.sampler, SamplerKind::Stratified);
+        assert_eq!(read.sequence, 64);
+        assert_eq!(read.filter, FilterKind::Tent);
+        assert_eq!(read.filter_radius, 1.0);
+        assert_eq!(read.film.rows(), 0..2);
+        assert_eq!(read.film.stats(1, 0), stats);
+        let colors = |film: &Film| {
+            let image = film.image();
+            image
+                .pixels()
+                .iter()
+                .map(|c| [c.r, c.g, c.b])
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(colors(&read.film), colors(&checkpoint.film));
+
+        assert!(Checkpoint::read(&mut &bytes[..bytes.len() - 1]).is_err());
+        // A damaged size fails on the missing pixels instead of allocating
+        // them all up front.
+        let mut huge = bytes.clone();
+        huge[38..42].copy_from_slice(&65536u32.to_le_bytes());
+        huge[46..50].copy_from_slice(&65536u32.to_le_bytes());
+        assert!(Checkpoint::read(&mut huge.as_slice()).is_err());
+        bytes[0] = b'R';
+        assert!(Checkpoint::read(&mut bytes.as_slice()).is_err());
+    }
+}
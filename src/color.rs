@@ -16,6 +16,7 @@ impl<T> Color<T> {
 }
 
 impl Color<f64> {
+    /// Gamma corrects and quantizes to 8 bits per channel.
     pub fn to_int(self) -> Color<u8> {
         let Self { r, g, b } = self;
         let [r, g, b] = [r, g, b].map(|c| {
@@ -25,6 +26,17 @@ impl Color<f64> {
         });
         Color::new(r, g, b)
     }
+
+    /// Gamma corrects and quantizes to 16 bits per channel.
+    pub fn to_u16(self) -> Color<u16> {
+        let Self { r, g, b } = self;
+        let [r, g, b] = [r, g, b].map(|c| {
+            const INTENSITY: Interval = Interval::new(0.0, 1.0);
+            let corrected = linear_to_gamma(c);
+            (65535.0 * INTENSITY.clamp(corrected)).round() as u16
+        });
+        Color::new(r, g, b)
+    }
 }
 
 impl From<Vec3> for Color {
@@ -43,3 +55,17 @@ impl<T: fmt::Display> fmt::Display for Color<T> {
 fn linear_to_gamma(linear: f64) -> f64 {
     if linear > 0.0 { linear.sqrt() } else { 0.0 }
 }
+
+/// Decodes an sRGB encoded value in `[0, 1]` to linear.
+pub fn srgb_to_linear(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Brightness of linear Rec. 709 radiance as perceived by the eye.
+pub fn luminance(Vec3 { x, y, z }: Vec3) -> f64 {
+    0.2126 * x + 0.7152 * y + 0.0722 * z
+}
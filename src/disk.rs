@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{HitRecord, HitTarget},
+    interval::Interval,
+    light::{DiskLight, Light},
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// Flat circle facing along its normal.
+pub struct Disk {
+    center: Vec3,
+    radius: f64,
+    basis: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self {
+            center,
+            radius,
+            basis: Onb::new(normal),
+            material,
+        }
+    }
+}
+
+impl HitTarget for Disk {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let t = intersect_disk(ray, ray_t, self.center, self.basis.w, self.radius)?;
+        let point = ray.at(t);
+
+        // Map the disk onto the unit square it's inscribed in.
+        let offset = (point - self.center) / (2.0 * self.radius);
+        let uv = (
+            0.5 + offset.dot(self.basis.u),
+            0.5 + offset.dot(self.basis.v),
+        );
+        let material = self.material.clone();
+        let mut hit = HitRecord::new(ray, point, self.basis.w, t, uv, material);
+        hit.uv_density = 1.0 / (2.0 * self.radius);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // Extent of the rim along each axis.
+        let n = self.basis.w;
+        let extent = |n: f64| self.radius * (1.0 - n * n).max(0.0).sqrt();
+        let e = Vec3::new(extent(n.x), extent(n.y), extent(n.z));
+        Aabb::from_points(self.center - e, self.center + e)
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        if self.material.is_emissive() {
+            let light = DiskLight::new(self.center, self.basis.w, self.radius);
+            lights.push(Arc::new(light));
+        }
+    }
+}
+
+/// Intersects the disk of `radius` around `center` facing along the unit
+/// vector `normal`, returning the ray parameter.
+pub fn intersect_disk(
+    ray: &Ray,
+    ray_t: Interval,
+    center: Vec3,
+    normal: Vec3,
+    radius: f64,
+) -> Option<f64> {
+    let denom = normal.dot(ray.direction);
+    if denom.abs() < 1e-12 * ray.direction.len() {
+        return None;
+    }
+    let t = normal.dot(center - ray.origin) / denom;
+    if !ray_t.surrounds(t) || (ray.at(t) - center).len_squared() > radius * radius {
+        return None;
+    }
+    Some(t)
+}
@@ -0,0 +1,345 @@
+//! Accumulation of filtered samples into pixels.
+
+use std::{
+    io::{self, Read, Write},
+    ops::Range,
+};
+
+use crate::{color::Color, filter::Filter, image::Image, vec3::Vec3};
+
+/// Weighted sums of the samples that land near each pixel, for all of an
+/// image or a band of its rows, along with statistics of the samples taken
+/// in each pixel.
+///
+/// Positions on the film are in pixels from the top left corner of the
+/// image, so pixel (i, j) covers `[i, i + 1)` by `[j, j + 1)`.
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    rows: Range<u32>,
+    pixels: Vec<FilmPixel>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct FilmPixel {
+    sum: Vec3,
+    weight: f64,
+    stats: Welford,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_rows(width, 0..height)
+    }
+
+    fn with_rows(width: u32, rows: Range<u32>) -> Self {
+        Self {
+            width,
+            pixels: vec![FilmPixel::default(); width as usize * rows.len()],
+            rows,
+        }
+    }
+
+    /// Empty band of a `width` by `height` film, covering every row that
+    /// `filter` spreads samples taken in row `j` onto.
+    pub fn band(width: u32, height: u32, j: u32, filter: &dyn Filter) -> Self {
+        let reach = filter.radius().ceil() as u32;
+        let start = j.saturating_sub(reach);
+        let end = j.saturating_add(reach).saturating_add(1).min(height);
+        Self::with_rows(width, start..end)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Rows of the image the film covers.
+    pub fn rows(&self) -> Range<u32> {
+        self.rows.clone()
+    }
+
+    /// Adds `radiance` seen at (`x`, `y`) to every pixel whose center is
+    /// within `filter`'s reach, weighted by the filter.
+    pub fn add_sample(&mut self, filter: &dyn Filter, (x, y): (f64, f64), radiance: Vec3) {
+        let radius = filter.radius();
+        let columns = reach(x, radius, 0..self.width);
+        for j in reach(y, radius, self.rows.clone()) {
+            for i in columns.clone() {
+                let weight = filter.weight(i as f64 + 0.5 - x, j as f64 + 0.5 - y);
+                if weight != 0.0 {
+                    let pixel = self.pixel_mut(i, j);
+                    pixel.sum = pixel.sum + weight * radiance;
+                    pixel.weight += weight;
+                }
+            }
+        }
+    }
+
+    /// Statistics of the samples taken in pixel (`i`, `j`).
+    pub fn stats(&self, i: u32, j: u32) -> Welford {
+        self.pixel(i, j).stats
+    }
+
+    /// Records statistics of samples taken in pixel (`i`, `j`), on top of
+    /// any already there.
+    pub fn add_stats(&mut self, i: u32, j: u32, stats: Welford) {
+        self.pixel_mut(i, j).stats.merge(stats);
+    }
+
+    /// Fewest samples taken in any pixel.
+    pub fn fewest_samples(&self) -> u32 {
+        self.pixels
+            .iter()
+            .map(|p| p.stats.count())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Adds the sums and statistics of a band of the same film.
+    pub fn merge(&mut self, band: &Film) {
+        for (n, pixel) in band.pixels.iter().enumerate() {
+            let (i, j) = band.coordinates(n);
+            let target = self.pixel_mut(i, j);
+            target.sum = target.sum + pixel.sum;
+            target.weight += pixel.weight;
+            target.stats.merge(pixel.stats);
+        }
+    }
+
+    /// Weighted average of the samples at each pixel. Negative lobes of the
+    /// filter can leave channels below zero, which are clamped.
+    pub fn image(&self) -> Image {
+        let mut image = Image::new(self.width, self.rows.len() as u32);
+        for j in self.rows.clone() {
+            let row = image.row_mut(j - self.rows.start);
+            for (i, color) in row.iter_mut().enumerate() {
+                let pixel = self.pixel(i as u32, j);
+                if pixel.weight > 0.0 {
+                    let Vec3 { x, y, z } = pixel.sum / pixel.weight;
+                    *color = Color::new(x.max(0.0), y.max(0.0), z.max(0.0));
+                }
+            }
+        }
+        image
+    }
+
+    /// Number of samples taken in each pixel as colors from black through
+    /// red and yellow to white at the most sampled pixel.
+    pub fn heatmap(&self) -> Image {
+        let max = self
+            .pixels
+            .iter()
+            .map(|p| p.stats.count())
+            .max()
+            .unwrap_or(0);
+        let mut image = Image::new(self.width, self.rows.len() as u32);
+        for j in self.rows.clone() {
+            let row = image.row_mut(j - self.rows.start);
+            for (i, color) in row.iter_mut().enumerate() {
+                let t = self.pixel(i as u32, j).stats.count() as f64 / max.max(1) as f64;
+                let ramp = |start: f64| (3.0 * t - start).clamp(0.0, 1.0);
+                *color = Color::new(ramp(0.0), ramp(1.0), ramp(2.0));
+            }
+        }
+        image
+    }
+
+    /// Writes the film losslessly, in little-endian binary.
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        for n in [self.width, self.rows.start, self.rows.end] {
+            out.write_all(&n.to_le_bytes())?;
+        }
+        for pixel in &self.pixels {
+            let Vec3 { x, y, z } = pixel.sum;
+            for value in [x, y, z, pixel.weight] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+            let Welford { count, mean, m2 } = pixel.stats;
+            out.write_all(&count.to_le_bytes())?;
+            out.write_all(&mean.to_le_bytes())?;
+            out.write_all(&m2.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a film written by [`Film::write`].
+    pub fn read(input: &mut dyn Read) -> io::Result<Self> {
+        let width = read_u32(input)?;
+        let rows = read_u32(input)?..read_u32(input)?;
+        if width == 0 || rows.is_empty() {
+            return Err(invalid_data("film has no pixels"));
+        }
+        let count = (width as usize)
+            .checked_mul(rows.len())
+            .ok_or_else(|| invalid_data("film is too large"))?;
+        // The pixels grow as they're read, so that a damaged size doesn't
+        // allocate more than the data holds.
+        let mut pixels = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            pixels.push(FilmPixel {
+                sum: Vec3::new(read_f64(input)?, read_f64(input)?, read_f64(input)?),
+                weight: read_f64(input)?,
+                stats: Welford {
+                    count: read_u32(input)?,
+                    mean: read_f64(input)?,
+                    m2: read_f64(input)?,
+                },
+            });
+        }
+        Ok(Self {
+            width,
+            rows,
+            pixels,
+        })
+    }
+
+    fn index(&self, i: u32, j: u32) -> usize {
+        (j - self.rows.start) as usize * self.width as usize + i as usize
+    }
+
+    fn coordinates(&self, n: usize) -> (u32, u32) {
+        let width = self.width as usize;
+        ((n % width) as u32, self.rows.start + (n / width) as u32)
+    }
+
+    fn pixel(&self, i: u32, j: u32) -> &FilmPixel {
+        &self.pixels[self.index(i, j)]
+    }
+
+    fn pixel_mut(&mut self, i: u32, j: u32) -> &mut FilmPixel {
+        let index = self.index(i, j);
+        &mut self.pixels[index]
+    }
+}
+
+/// Running count, mean and variance of a stream of values, updated with
+/// Welford's algorithm.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Welford {
+    count: u32,
+    mean: f64,
+    /// Sum of squared differences from the mean.
+    m2: f64,
+}
+
+impl Welford {
+    pub fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Combines the statistics of two separate streams.
+    pub fn merge(&mut self, other: Welford) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let weight = other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * self.count as f64 * weight;
+        self.mean += delta * weight;
+        self.count = count;
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Unbiased estimate of the variance of the values.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// Estimated standard deviation of the mean.
+    pub fn standard_error(&self) -> f64 {
+        (self.variance() / self.count.max(1) as f64).sqrt()
+    }
+}
+
+fn read_u32(input: &mut dyn Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f64(input: &mut dyn Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Pixels within `bounds` whose centers lie within `radius` of `x`.
+fn reach(x: f64, radius: f64, bounds: Range<u32>) -> Range<u32> {
+    let start = (x - 0.5 - radius).ceil().max(bounds.start as f64) as u32;
+    let end = ((x - 0.5 + radius).floor() + 1.0).min(bounds.end as f64) as u32;
+    start..end.max(start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{BoxFilter, TentFilter};
+
+    #[test]
+    fn box_filter_averages_each_pixel() {
+        let filter = BoxFilter::new(0.5);
+        let mut film = Film::new(2, 1);
+        film.add_sample(&filter, (0.2, 0.5), Vec3::splat(1.0));
+        film.add_sample(&filter, (0.9, 0.1), Vec3::splat(3.0));
+        film.add_sample(&filter, (1.5, 0.5), Vec3::splat(5.0));
+        let image = film.image();
+        assert_eq!(image.pixels()[0].r, 2.0);
+        assert_eq!(image.pixels()[1].r, 5.0);
+    }
+
+    #[test]
+    fn bands_splat_into_neighbours() {
+        let filter = TentFilter::new(1.0);
+        let mut film = Film::new(3, 3);
+        let mut band = Film::band(3, 3, 1, &filter);
+        assert_eq!(band.rows, 0..3);
+        // Halfway between the centers of the middle pixel and the one to
+        // its right, and level with them.
+        band.add_sample(&filter, (2.0, 1.5), Vec3::splat(1.0));
+        film.merge(&band);
+        let weights = film.pixels.iter().map(|p| p.weight).collect::<Vec<_>>();
+        assert_eq!(weights, [0.0, 0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0]);
+        assert_eq!(Film::band(3, 3, 0, &filter).rows, 0..2);
+        let wide = TentFilter::new(1e10);
+        assert_eq!(Film::band(3, 3, 1, &wide).rows, 0..3);
+    }
+
+    #[test]
+    fn welford_matches_direct_statistics() {
+        let values = [0.5, 2.0, 3.5, 1.0, 8.0, 0.0, 4.5];
+        let mean = values.iter().sum::<f64>() / 7.0;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 6.0;
+
+        let mut all = Welford::default();
+        values.iter().for_each(|&x| all.add(x));
+        let (mut left, mut right) = (Welford::default(), Welford::default());
+        values[..3].iter().for_each(|&x| left.add(x));
+        values[3..].iter().for_each(|&x| right.add(x));
+        left.merge(right);
+
+        for stats in [all, left] {
+            assert_eq!(stats.count(), 7);
+            assert!((stats.mean() - mean).abs() < 1e-12);
+            assert!((stats.variance() - variance).abs() < 1e-12);
+        }
+    }
+}
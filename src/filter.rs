@@ -0,0 +1,237 @@
+//! Reconstruction filters that weigh samples by their distance from pixel
+//! centers.
+
+use std::f64::consts::PI;
+
+/// Weight of a sample for a pixel, by its offset from the pixel's center.
+pub trait Filter: Send + Sync {
+    /// Offset along either axis, in pixels, beyond which the weight is zero.
+    fn radius(&self) -> f64;
+
+    /// Weight of a sample `x` and `y` pixels from the center, which may be
+    /// negative.
+    fn weight(&self, x: f64, y: f64) -> f64;
+}
+
+/// Equal weight for every sample within the radius. With a radius of half a
+/// pixel, each pixel averages the samples inside it.
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn weight(&self, x: f64, y: f64) -> f64 {
+        if x.abs() <= self.radius && y.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Weight falling linearly from the center to zero at the radius.
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn weight(&self, x: f64, y: f64) -> f64 {
+        let tent = |x: f64| (self.radius - x.abs()).max(0.0);
+        tent(x) * tent(y)
+    }
+}
+
+/// Gaussian with a standard deviation of a third of the radius, shifted down
+/// to reach zero at the radius.
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64) -> Self {
+        Self {
+            radius,
+            sigma: radius / 3.0,
+        }
+    }
+
+    fn gaussian(&self, x: f64) -> f64 {
+        (-x * x / (2.0 * self.sigma * self.sigma)).exp()
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn weight(&self, x: f64, y: f64) -> f64 {
+        let edge = self.gaussian(self.radius);
+        let gaussian = |x: f64| (self.gaussian(x) - edge).max(0.0);
+        gaussian(x) * gaussian(y)
+    }
+}
+
+/// Mitchell-Netravali cubic with B = C = 1/3, stretched over the radius.
+/// Its negative lobes sharpen edges.
+pub struct MitchellFilter {
+    radius: f64,
+}
+
+impl MitchellFilter {
+    const B: f64 = 1.0 / 3.0;
+    const C: f64 = 1.0 / 3.0;
+
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+
+    /// The cubic, which spans `[-2, 2]`.
+    fn cubic(x: f64) -> f64 {
+        let (b, c) = (Self::B, Self::C);
+        let x = x.abs();
+        let value = if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            (-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x - (12.0 * b + 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)
+        } else {
+            (12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)
+        };
+        value / 6.0
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn weight(&self, x: f64, y: f64) -> f64 {
+        let scale = 2.0 / self.radius;
+        Self::cubic(x * scale) * Self::cubic(y * scale)
+    }
+}
+
+/// Sinc windowed by a wider sinc that reaches zero at the radius.
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+
+    fn lanczos(&self, x: f64) -> f64 {
+        if x.abs() >= self.radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.radius)
+        }
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn weight(&self, x: f64, y: f64) -> f64 {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Kind of [`Filter`] to reconstruct pixels with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterKind {
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterKind {
+    /// Radius in pixels the filter uses unless told otherwise.
+    pub fn default_radius(self) -> f64 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+
+    pub fn build(self, radius: f64) -> Box<dyn Filter> {
+        match self {
+            FilterKind::Box => Box::new(BoxFilter::new(radius)),
+            FilterKind::Tent => Box::new(TentFilter::new(radius)),
+            FilterKind::Gaussian => Box::new(GaussianFilter::new(radius)),
+            FilterKind::Mitchell => Box::new(MitchellFilter::new(radius)),
+            FilterKind::Lanczos => Box::new(LanczosFilter::new(radius)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_vanish_at_radius() {
+        for kind in [
+            FilterKind::Box,
+            FilterKind::Tent,
+            FilterKind::Gaussian,
+            FilterKind::Mitchell,
+            FilterKind::Lanczos,
+        ] {
+            for radius in [kind.default_radius(), 1.25] {
+                let filter = kind.build(radius);
+                assert!(filter.weight(0.0, 0.0) > 0.0, "{kind:?}");
+                assert_eq!(filter.weight(radius * 1.01, 0.0), 0.0, "{kind:?}");
+                assert_eq!(filter.weight(0.0, -radius * 1.01), 0.0, "{kind:?}");
+                if kind != FilterKind::Box {
+                    assert!(filter.weight(radius, 0.0).abs() < 1e-9, "{kind:?}");
+                }
+            }
+        }
+        // The sharpening filters dip below zero away from the center.
+        assert!(MitchellFilter::new(2.0).weight(1.5, 0.0) < 0.0);
+        assert!(LanczosFilter::new(3.0).weight(1.5, 0.0) < 0.0);
+    }
+}
@@ -0,0 +1,264 @@
+//! Voxel grids of density for heterogeneous media.
+//!
+//! Grids are read from the binary `.vol` format used by Mitsuba: the bytes
+//! `VOL` and version 3, then little-endian 32-bit integers for the encoding
+//! (1 for 32-bit floats, 3 for bytes), the x, y and z resolutions and the
+//! channel count (which has to be 1), six 32-bit floats for the minimum and
+//! maximum corners of the grid, and finally the values with x varying
+//! fastest and z slowest.
+
+use std::{fs, io, path::Path};
+
+use crate::{aabb::Aabb, medium::Density, vec3::Vec3};
+
+/// Edge length of the cubes of voxels the grid is stored in.
+const BRICK: usize = 8;
+
+/// Density sampled at the centers of a regular grid of voxels spanning a
+/// box. Voxels are stored in bricks, and bricks that are entirely empty take
+/// no memory.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    bbox: Aabb,
+    bricks: Vec<Option<Box<[f32]>>>,
+    brick_counts: [usize; 3],
+    max: f64,
+}
+
+impl VoxelGrid {
+    /// Grid of `values` ordered with x varying fastest, spanning `bbox`.
+    ///
+    /// # Panics
+    ///
+    /// If any resolution is zero or there isn't one value per voxel.
+    pub fn new(resolution: [usize; 3], values: &[f32], bbox: Aabb) -> Self {
+        assert!(resolution.iter().all(|&n| n > 0), "empty grid");
+        let [nx, ny, nz] = resolution;
+        assert_eq!(values.len(), nx * ny * nz, "wrong number of voxels");
+
+        let brick_counts = resolution.map(|n| n.div_ceil(BRICK));
+        let [bx, by, bz] = brick_counts;
+        let mut bricks = Vec::with_capacity(bx * by * bz);
+        let mut max = 0.0f32;
+        for k in 0..bz {
+            for j in 0..by {
+                for i in 0..bx {
+                    let mut brick = vec![0.0; BRICK * BRICK * BRICK];
+                    let mut empty = true;
+                    for (n, value) in brick.iter_mut().enumerate() {
+                        let (x, y, z) = (
+                            i * BRICK + n % BRICK,
+                            j * BRICK + n / BRICK % BRICK,
+                            k * BRICK + n / (BRICK * BRICK),
+                        );
+                        if x < nx && y < ny && z < nz {
+                            *value = values[(z * ny + y) * nx + x].max(0.0);
+                            empty &= *value == 0.0;
+                            max = max.max(*value);
+                        }
+                    }
+                    bricks.push((!empty).then(|| brick.into_boxed_slice()));
+                }
+            }
+        }
+
+        Self {
+            resolution,
+            bbox,
+            bricks,
+            brick_counts,
+            max: max as f64,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        decode(&fs::read(path)?)
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        let [bx, by, _] = self.brick_counts;
+        let brick = ((z / BRICK) * by + y / BRICK) * bx + x / BRICK;
+        self.bricks[brick].as_ref().map_or(0.0, |brick| {
+            brick[((z % BRICK) * BRICK + y % BRICK) * BRICK + x % BRICK]
+        })
+    }
+}
+
+impl Density for VoxelGrid {
+    /// Trilinear blend of the nearest voxel centers, held constant past the
+    /// outermost centers and zero outside the box.
+    fn density(&self, p: Vec3) -> f64 {
+        let (min, max) = (self.bbox.min(), self.bbox.max());
+        if (0..3).any(|n| p[n] < min[n] || p[n] > max[n]) {
+            return 0.0;
+        }
+
+        let mut corners = [[0; 2]; 3];
+        let mut weights = [0.0; 3];
+        for n in 0..3 {
+            let size = self.resolution[n];
+            let x = (p[n] - min[n]) / (max[n] - min[n]) * size as f64 - 0.5;
+            let x = x.clamp(0.0, (size - 1) as f64);
+            let i = (x as usize).min(size - 1);
+            corners[n] = [i, (i + 1).min(size - 1)];
+            weights[n] = x - i as f64;
+        }
+
+        let mut sum = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for n in 0..3 {
+                let high = (corner >> n) & 1;
+                index[n] = corners[n][high];
+                weight *= if high == 1 {
+                    weights[n]
+                } else {
+                    1.0 - weights[n]
+                };
+            }
+            sum += weight * self.voxel(index[0], index[1], index[2]) as f64;
+        }
+        sum
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+fn decode(bytes: &[u8]) -> io::Result<VoxelGrid> {
+    let mut reader = Reader(bytes);
+    if reader.take(4)? != b"VOL\x03" {
+        return Err(invalid_data("not a version 3 VOL grid"));
+    }
+    let encoding = reader.u32()?;
+    let resolution = [reader.u32()?, reader.u32()?, reader.u32()?];
+    let channels = reader.u32()?;
+    if channels != 1 {
+        return Err(invalid_data(format!(
+            "grid has {channels} channels, expected 1"
+        )));
+    }
+    if resolution.contains(&0) {
+        return Err(invalid_data("grid has no voxels"));
+    }
+    let mut corners = [0.0f32; 6];
+    for corner in &mut corners {
+        *corner = reader.f32()?;
+    }
+    let bbox = Aabb::from_points(
+        Vec3::new(corners[0] as f64, corners[1] as f64, corners[2] as f64),
+        Vec3::new(corners[3] as f64, corners[4] as f64, corners[5] as f64),
+    );
+    if !(0..3).all(|n| bbox.axis(n).size() > 0.0) || !bbox.is_bounded() {
+        return Err(invalid_data("grid box must be finite with some volume"));
+    }
+
+    let resolution = resolution.map(|n| n as usize);
+    let count = resolution
+        .iter()
+        .try_fold(1usize, |count, &n| count.checked_mul(n))
+        .ok_or_else(|| invalid_data("grid is too large"))?;
+    let values = match encoding {
+        1 => reader
+            .take(
+                count
+                    .checked_mul(4)
+                    .ok_or_else(|| invalid_data("grid is too large"))?,
+            )?
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<_>>(),
+        3 => reader
+            .take(count)?
+            .iter()
+            .map(|&b| b as f32 / 255.0)
+            .collect(),
+        _ => {
+            return Err(invalid_data(format!(
+                "unsupported grid encoding {encoding}"
+            )));
+        }
+    };
+    if values.iter().any(|v| !v.is_finite()) {
+        return Err(invalid_data("grid has a value that isn't finite"));
+    }
+    Ok(VoxelGrid::new(resolution, &values, bbox))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let (head, rest) = self
+            .0
+            .split_at_checked(n)
+            .ok_or_else(|| invalid_data("grid ends early"))?;
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(resolution: [u32; 3], values: &[f32]) -> Vec<u8> {
+        let mut bytes = b"VOL\x03".to_vec();
+        for n in [1, resolution[0], resolution[1], resolution[2], 1] {
+            bytes.extend(u32::to_le_bytes(n));
+        }
+        for corner in [0.0f32, 0.0, 0.0, 2.0, 1.0, 1.0] {
+            bytes.extend(corner.to_le_bytes());
+        }
+        for value in values {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_and_interpolates() {
+        // Two voxels side by side along x, centered at x = 0.5 and 1.5.
+        let grid = decode(&encode([2, 1, 1], &[1.0, 3.0])).unwrap();
+        assert_eq!(grid.max_density(), 3.0);
+        let at = |x| grid.density(Vec3::new(x, 0.5, 0.5));
+        assert_eq!(at(0.25), 1.0);
+        assert_eq!(at(1.0), 2.0);
+        assert_eq!(at(1.75), 3.0);
+        assert_eq!(at(2.5), 0.0);
+
+        let truncated = encode([2, 1, 1], &[1.0]);
+        assert!(decode(&truncated).is_err());
+    }
+
+    #[test]
+    fn skips_empty_bricks() {
+        let n = 2 * BRICK;
+        let mut values = vec![0.0; n * n * n];
+        values[n * n * n - 1] = 1.0;
+        let bbox = Aabb::from_points(Vec3::splat(0.0), Vec3::splat(1.0));
+        let grid = VoxelGrid::new([n; 3], &values, bbox);
+        assert_eq!(grid.bricks.iter().filter(|b| b.is_some()).count(), 1);
+        assert_eq!(grid.voxel(n - 1, n - 1, n - 1), 1.0);
+        assert_eq!(grid.voxel(0, 0, 0), 0.0);
+    }
+}
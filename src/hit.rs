@@ -1,24 +1,44 @@
 use std::sync::Arc;
 
-use crate::{interval::Interval, material::Material, ray::Ray, vec3::Vec3};
+use crate::{
+    aabb::Aabb, bvh::Bvh, interval::Interval, light::Light, material::Material, ray::Ray,
+    vec3::Vec3,
+};
 
 pub struct HitRecord {
     pub point: Vec3,
     pub normal: Vec3,
     pub t: f64,
     pub front_face: bool,
+    /// Surface coordinates of `point`, for texturing.
+    pub u: f64,
+    pub v: f64,
+    /// Change in `u, v` per unit of distance along the surface, or zero if
+    /// unknown.
+    pub uv_density: f64,
+    /// Width of the ray's cone where it meets the surface, stretched by the
+    /// angle of incidence.
+    pub footprint: f64,
     pub material: Arc<dyn Material>,
 }
 
 impl HitRecord {
+    /// Width of the ray's footprint in `u, v` units.
+    pub fn uv_footprint(&self) -> f64 {
+        self.footprint * self.uv_density
+    }
+
     pub fn new(
         ray: &Ray,
         point: Vec3,
         outward_normal: Vec3,
         t: f64,
+        (u, v): (f64, f64),
         material: Arc<dyn Material>,
     ) -> Self {
-        let front_face = ray.direction.dot(outward_normal) < 0.0;
+        let cosine = ray.direction.unit().dot(outward_normal);
+        let front_face = cosine < 0.0;
+        let footprint = ray.width_at(t) / cosine.abs().max(1e-3);
         let normal = if front_face {
             outward_normal
         } else {
@@ -30,35 +50,123 @@ impl HitRecord {
             normal,
             t,
             front_face,
+            u,
+            v,
+            uv_density: 0.0,
+            footprint,
             material,
         }
     }
 }
 
 pub trait HitTarget: Send + Sync {
+    /// Closest surface the ray meets within `ray_t`, or the first point at
+    /// which it scatters or is absorbed inside a participating medium.
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord>;
+
+    /// Like [`HitTarget::hit`], but passing through participating media, so
+    /// that shadow rays can account for them with
+    /// [`HitTarget::transmittance`] instead.
+    fn hit_surface(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.hit(ray, ray_t)
+    }
+
+    /// Fraction of light that makes it through the participating media along
+    /// `ray` within `ray_t`. Surfaces don't count.
+    fn transmittance(&self, _ray: &Ray, _ray_t: Interval) -> f64 {
+        1.0
+    }
+
+    fn bounding_box(&self) -> Aabb;
+
+    /// Adds any emissive parts of the target that can be sampled directly.
+    fn lights(&self, _lights: &mut Vec<Arc<dyn Light>>) {}
+}
+
+impl<T: HitTarget + ?Sized> HitTarget for Box<T> {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        (**self).hit(ray, ray_t)
+    }
+
+    fn hit_surface(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        (**self).hit_surface(ray, ray_t)
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f64 {
+        (**self).transmittance(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        (**self).lights(lights)
+    }
+}
+
+impl<T: HitTarget + ?Sized> HitTarget for Arc<T> {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        (**self).hit(ray, ray_t)
+    }
+
+    fn hit_surface(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        (**self).hit_surface(ray, ray_t)
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f64 {
+        (**self).transmittance(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        (**self).lights(lights)
+    }
 }
 
 pub struct HitWorld {
     list: Vec<Box<dyn HitTarget>>,
+    bbox: Aabb,
 }
 
 impl HitWorld {
     pub fn new() -> Self {
-        Self { list: Vec::new() }
+        Self {
+            list: Vec::new(),
+            bbox: Aabb::EMPTY,
+        }
     }
 
     pub fn push(&mut self, target: impl HitTarget + 'static) {
+        self.bbox = self.bbox.union(target.bounding_box());
         self.list.push(Box::new(target));
     }
+
+    /// Builds an acceleration structure over everything pushed so far.
+    pub fn into_bvh(self) -> Bvh {
+        Bvh::new(self.list)
+    }
 }
 
-impl HitTarget for HitWorld {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+impl Default for HitWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HitWorld {
+    fn closest(
+        &self,
+        ray_t: Interval,
+        hit: impl Fn(&dyn HitTarget, Interval) -> Option<HitRecord>,
+    ) -> Option<HitRecord> {
         let mut closest_so_far = ray_t.max;
         let mut last_hit = None;
         for target in self.list.iter() {
-            if let Some(hit) = target.hit(ray, ray_t.with_max(closest_so_far)) {
+            if let Some(hit) = hit(target, ray_t.with_max(closest_so_far)) {
                 closest_so_far = hit.t;
                 last_hit.replace(hit);
             }
@@ -66,3 +174,30 @@ impl HitTarget for HitWorld {
         last_hit
     }
 }
+
+impl HitTarget for HitWorld {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.closest(ray_t, |target, ray_t| target.hit(ray, ray_t))
+    }
+
+    fn hit_surface(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.closest(ray_t, |target, ray_t| target.hit_surface(ray, ray_t))
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f64 {
+        self.list
+            .iter()
+            .map(|target| target.transmittance(ray, ray_t))
+            .product()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        for target in self.list.iter() {
+            target.lights(lights);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! In-memory framebuffers and the encoders that write them out.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::color::Color;
+
+mod exr;
+mod hdr;
+mod pfm;
+mod png;
+mod ppm;
+
+pub use self::{
+    exr::{ExrCompression, ExrEncoder, ExrPrecision},
+    hdr::HdrEncoder,
+    pfm::PfmEncoder,
+    png::PngEncoder,
+    ppm::PpmEncoder,
+};
+
+/// Linear radiance, stored row by row from the top left.
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::default(); width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn row_mut(&mut self, j: u32) -> &mut [Color] {
+        let start = j as usize * self.width as usize;
+        &mut self.pixels[start..start + self.width as usize]
+    }
+
+    pub fn write(&self, format: ImageFormat, out: &mut dyn Write) -> io::Result<()> {
+        format.encoder().encode(self, out)
+    }
+
+    /// Reads a PNG, Netpbm or Radiance HDR file, picked by extension. 8 and
+    /// 16-bit images are taken to be sRGB encoded and converted to linear.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        let decode = match extension.as_deref() {
+            Some("png") => png::decode,
+            Some("ppm" | "pgm" | "pnm") => ppm::decode,
+            Some("hdr") => hdr::decode,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "unsupported image format, expected .png, .ppm, .pgm or .hdr",
+                ));
+            }
+        };
+        decode(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: ImageFormat) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(format, &mut out)?;
+        out.flush()
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+pub trait ImageEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// ASCII PPM (P3)
+    PpmAscii,
+    /// Binary PPM (P6)
+    Ppm,
+    /// 8-bit PNG
+    Png,
+    /// 16-bit PNG
+    Png16,
+    /// Radiance RGBE
+    Hdr,
+    /// Portable float map
+    Pfm,
+    /// OpenEXR
+    Exr(ExrEncoder),
+}
+
+impl ImageFormat {
+    /// Guesses the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(Self::Ppm),
+            "png" => Some(Self::Png),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            "exr" => Some(Self::Exr(ExrEncoder::default())),
+            _ => None,
+        }
+    }
+
+    pub fn encoder(self) -> Box<dyn ImageEncoder> {
+        match self {
+            Self::PpmAscii => Box::new(PpmEncoder::Ascii),
+            Self::Ppm => Box::new(PpmEncoder::Binary),
+            Self::Png => Box::new(PngEncoder::Rgb8),
+            Self::Png16 => Box::new(PngEncoder::Rgb16),
+            Self::Hdr => Box::new(HdrEncoder),
+            Self::Pfm => Box::new(PfmEncoder),
+            Self::Exr(encoder) => Box::new(encoder),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image {
+        let mut image = Image::new(2, 1);
+        image
+            .row_mut(0)
+            .copy_from_slice(&[Color::new(1.0, 0.25, 0.0), Color::new(0.0, 0.0, 4.0)]);
+        image
+    }
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let mut out = Vec::new();
+        image().write(format, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn ppm() {
+        let ascii = encode(ImageFormat::PpmAscii);
+        assert_eq!(
+            String::from_utf8(ascii).unwrap(),
+            "P3\n2 1\n255\n255 128 0\n0 0 255\n"
+        );
+        let binary = encode(ImageFormat::Ppm);
+        assert_eq!(binary, b"P6\n2 1\n255\n\xff\x80\x00\x00\x00\xff");
+    }
+
+    #[test]
+    fn decodes_ppm() {
+        let image = ppm::decode(b"P3\n# comment\n2 1\n255\n255 0 0 0 0 255\n").unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        let [a, b] = [image.pixels()[0], image.pixels()[1]];
+        assert_eq!((a.r, a.g, a.b), (1.0, 0.0, 0.0));
+        assert_eq!((b.r, b.g, b.b), (0.0, 0.0, 1.0));
+
+        let image = ppm::decode(b"P5 1 1 65535\n\x80\x00").unwrap();
+        let pixel = image.pixels()[0];
+        assert!((pixel.r - 0.214).abs() < 1e-3);
+        assert_eq!((pixel.r, pixel.r), (pixel.g, pixel.b));
+
+        for header in [
+            "P6 4294967295 4294967295 255\n",
+            "P3 100000 100000 255\n1 2 3",
+        ] {
+            let error = ppm::decode(header.as_bytes()).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn hdr_round_trip() {
+        let mut image = Image::new(10, 2);
+        for (i, pixel) in image.pixels.iter_mut().enumerate() {
+            *pixel = Color::new(i as f64, 0.5, 1.0 / (i + 1) as f64);
+        }
+        let mut data = Vec::new();
+        image.write(ImageFormat::Hdr, &mut data).unwrap();
+        let decoded = hdr::decode(&data).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (10, 2));
+        for (a, b) in image.pixels().iter().zip(decoded.pixels()) {
+            let error = (a.r - b.r)
+                .abs()
+                .max((a.g - b.g).abs())
+                .max((a.b - b.b).abs());
+            assert!(error < 0.04 * a.r.max(a.g).max(a.b), "{a} decoded as {b}");
+        }
+    }
+
+    #[test]
+    fn png_round_trip() {
+        for (format, expected) in [
+            (ImageFormat::Png, vec![255, 128, 0, 0, 0, 255]),
+            (
+                ImageFormat::Png16,
+                vec![255, 255, 128, 0, 0, 0, 0, 0, 0, 0, 255, 255],
+            ),
+        ] {
+            let data = encode(format);
+            let mut reader = ::png::Decoder::new(io::Cursor::new(data))
+                .read_info()
+                .unwrap();
+            let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+            let info = reader.next_frame(&mut buf).unwrap();
+            assert_eq!((info.width, info.height), (2, 1));
+            assert_eq!(&buf[..info.buffer_size()], expected);
+
+            let decoded = png::decode(&encode(format)).unwrap();
+            let pixel = decoded.pixels()[0];
+            assert_eq!((pixel.r, pixel.b), (1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn pfm() {
+        let data = encode(ImageFormat::Pfm);
+        let (header, pixels) = data.split_at(data.len() - 24);
+        assert_eq!(header, b"PF\n2 1\n-1.0\n");
+        assert_eq!(pixels[20..], 4f32.to_le_bytes());
+    }
+
+    #[test]
+    fn exr_round_trip() {
+        for precision in [ExrPrecision::Half, ExrPrecision::Float] {
+            for compression in [ExrCompression::None, ExrCompression::Zip] {
+                let encoder = ExrEncoder {
+                    precision,
+                    compression,
+                };
+                let data = encode(ImageFormat::Exr(encoder));
+                use ::exr::prelude::{ReadChannels, ReadLayers};
+                let image = ::exr::prelude::read()
+                    .no_deep_data()
+                    .largest_resolution_level()
+                    .rgb_channels(
+                        |size, _| vec![(0.0, 0.0, 0.0); size.area()],
+                        |pixels: &mut Vec<(f32, f32, f32)>, position, pixel| {
+                            pixels[position.y() * 2 + position.x()] = pixel
+                        },
+                    )
+                    .first_valid_layer()
+                    .all_attributes()
+                    .from_buffered(io::Cursor::new(data))
+                    .unwrap();
+                let pixels = image.layer_data.channel_data.pixels;
+                assert_eq!(pixels, [(1.0, 0.25, 0.0), (0.0, 0.0, 4.0)]);
+            }
+        }
+    }
+}
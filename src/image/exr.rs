@@ -0,0 +1,79 @@
+use std::io::{self, Cursor, Write};
+
+use ::exr::prelude::{self as exr, WritableImage};
+
+use super::{Image, ImageEncoder};
+
+/// Scanline OpenEXR image with R, G and B channels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExrEncoder {
+    pub precision: ExrPrecision,
+    pub compression: ExrCompression,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExrPrecision {
+    /// 16-bit floats.
+    #[default]
+    Half,
+    /// 32-bit floats.
+    Float,
+}
+
+/// Lossless compression methods.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExrCompression {
+    None,
+    Rle,
+    #[default]
+    Zip,
+    Piz,
+}
+
+impl ImageEncoder for ExrEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        let compression = match self.compression {
+            ExrCompression::None => exr::Compression::Uncompressed,
+            ExrCompression::Rle => exr::Compression::RLE,
+            ExrCompression::Zip => exr::Compression::ZIP16,
+            ExrCompression::Piz => exr::Compression::PIZ,
+        };
+        let encoding = exr::Encoding {
+            compression,
+            blocks: exr::Blocks::ScanLines,
+            line_order: exr::LineOrder::Increasing,
+        };
+        let size = (image.width() as usize, image.height() as usize);
+        let pixel =
+            |position: exr::Vec2<usize>| image.pixels()[position.y() * size.0 + position.x()];
+
+        // The writer needs to seek, so encode in memory first.
+        let mut buffer = Cursor::new(Vec::new());
+        let attributes = exr::LayerAttributes::default();
+        let result = match self.precision {
+            ExrPrecision::Half => {
+                let channels = exr::SpecificChannels::rgb(|position| {
+                    let color = pixel(position);
+                    let half = exr::f16::from_f64;
+                    (half(color.r), half(color.g), half(color.b))
+                });
+                let layer = exr::Layer::new(size, attributes, encoding, channels);
+                exr::Image::from_layer(layer)
+                    .write()
+                    .to_buffered(&mut buffer)
+            }
+            ExrPrecision::Float => {
+                let channels = exr::SpecificChannels::rgb(|position| {
+                    let color = pixel(position);
+                    (color.r as f32, color.g as f32, color.b as f32)
+                });
+                let layer = exr::Layer::new(size, attributes, encoding, channels);
+                exr::Image::from_layer(layer)
+                    .write()
+                    .to_buffered(&mut buffer)
+            }
+        };
+        result.map_err(io::Error::other)?;
+        out.write_all(buffer.get_ref())
+    }
+}
@@ -0,0 +1,275 @@
+use std::io::{self, Write};
+
+use super::{Image, ImageEncoder, invalid_data};
+use crate::color::Color;
+
+/// Radiance RGBE image with run-length encoded scanlines.
+#[derive(Debug, Clone, Copy)]
+pub struct HdrEncoder;
+
+impl ImageEncoder for HdrEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        write!(
+            out,
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            image.height(),
+            image.width()
+        )?;
+
+        let width = image.width() as usize;
+        let mut component = Vec::with_capacity(width);
+        for row in image.pixels().chunks(width) {
+            let rgbe = row.iter().map(|&color| to_rgbe(color)).collect::<Vec<_>>();
+            // The run-length encoding can only describe these widths.
+            if !(8..=0x7fff).contains(&width) {
+                out.write_all(rgbe.as_flattened())?;
+                continue;
+            }
+
+            out.write_all(&[2, 2, (width >> 8) as u8, width as u8])?;
+            for c in 0..4 {
+                component.clear();
+                component.extend(rgbe.iter().map(|p| p[c]));
+                write_rle(&component, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shared exponent encoding of a linear color.
+fn to_rgbe(color: Color) -> [u8; 4] {
+    let Color { r, g, b } = color;
+    let [r, g, b] = [r, g, b].map(|c| c.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0; 4];
+    }
+    // v = m * 2^e with m in [0.5, 1).
+    let e = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(e);
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+/// Writes one channel of a scanline as runs of equal bytes and literal
+/// stretches of differing ones.
+fn write_rle(data: &[u8], out: &mut dyn Write) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+    let mut cur = 0;
+    while cur < data.len() {
+        // Find the start of the next run that is worth encoding.
+        let mut run_start = cur;
+        let mut run_len = 0;
+        while run_len < MIN_RUN && run_start < data.len() {
+            run_start += run_len;
+            run_len = 1;
+            while run_start + run_len < data.len()
+                && run_len < 127
+                && data[run_start + run_len] == data[run_start]
+            {
+                run_len += 1;
+            }
+        }
+
+        while cur < run_start {
+            let literal_len = (run_start - cur).min(128);
+            out.write_all(&[literal_len as u8])?;
+            out.write_all(&data[cur..cur + literal_len])?;
+            cur += literal_len;
+        }
+
+        if run_len >= MIN_RUN {
+            out.write_all(&[128 + run_len as u8, data[run_start]])?;
+            cur += run_len;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a Radiance RGBE image stored top to bottom, with flat or run-length
+/// encoded scanlines.
+pub(super) fn decode(data: &[u8]) -> io::Result<Image> {
+    let mut lines = HeaderLines { data, pos: 0 };
+    let magic = lines.next()?;
+    if magic != b"#?RADIANCE" && magic != b"#?RGBE" {
+        return Err(invalid_data("not a Radiance HDR image"));
+    }
+    loop {
+        let line = lines.next()?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix(b"FORMAT=")
+            && format != b"32-bit_rle_rgbe"
+        {
+            return Err(invalid_data(format!(
+                "unsupported pixel format `{}`",
+                format.escape_ascii()
+            )));
+        }
+    }
+
+    let resolution = lines.next()?;
+    let (height, width) = std::str::from_utf8(resolution)
+        .ok()
+        .and_then(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["-Y", h, "+X", w] => Some((h.parse::<u32>().ok()?, w.parse::<u32>().ok()?)),
+                _ => None,
+            },
+        )
+        .ok_or_else(|| {
+            invalid_data(format!(
+                "unsupported resolution `{}`, expected `-Y <height> +X <width>`",
+                resolution.escape_ascii()
+            ))
+        })?;
+
+    let mut data = &data[lines.pos..];
+    let width = width as usize;
+    // Check the header against the data before allocating anything for it.
+    // Run-length encoded scanlines take at least two bytes per channel for
+    // every 127 pixels, and flat ones four bytes per pixel.
+    let scanline_bytes = if (8..=0x7fff).contains(&width) {
+        4 + 8 * width.div_ceil(127)
+    } else {
+        4 * width
+    };
+    let count = width
+        .checked_mul(height as usize)
+        .ok_or_else(|| invalid_data("image is too large"))?;
+    if scanline_bytes.saturating_mul(height as usize) > data.len() {
+        return Err(invalid_data("image data is truncated"));
+    }
+    // Compressed images can have many more pixels than bytes, so the
+    // pixels grow with the data rather than trusting the header.
+    let mut pixels = Vec::with_capacity(count.min(data.len()));
+    let mut rgbe = vec![[0; 4]; width];
+    for _ in 0..height {
+        let is_rle = (8..=0x7fff).contains(&width)
+            && data.len() >= 4
+            && data[..2] == [2, 2]
+            && usize::from(data[2]) << 8 | usize::from(data[3]) == width;
+        if is_rle {
+            data = &data[4..];
+            for c in 0..4 {
+                data = read_rle(data, &mut rgbe, c)?;
+            }
+        } else {
+            let bytes = data
+                .get(..4 * width)
+                .ok_or_else(|| invalid_data("image data is truncated"))?;
+            for (pixel, bytes) in rgbe.iter_mut().zip(bytes.chunks_exact(4)) {
+                pixel.copy_from_slice(bytes);
+            }
+            data = &data[4 * width..];
+        }
+        pixels.extend(rgbe.iter().map(|&p| from_rgbe(p)));
+    }
+
+    Ok(Image {
+        width: width as u32,
+        height,
+        pixels,
+    })
+}
+
+fn from_rgbe([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return Color::default();
+    }
+    let scale = 2f64.powi(e as i32 - 136);
+    Color::new(
+        (r as f64 + 0.5) * scale,
+        (g as f64 + 0.5) * scale,
+        (b as f64 + 0.5) * scale,
+    )
+}
+
+/// Decodes channel `c` of a run-length encoded scanline, returning the rest
+/// of the data.
+fn read_rle<'a>(mut data: &'a [u8], rgbe: &mut [[u8; 4]], c: usize) -> io::Result<&'a [u8]> {
+    let truncated = || invalid_data("image data is truncated");
+    let mut x = 0;
+    while x < rgbe.len() {
+        let (&count, rest) = data.split_first().ok_or_else(truncated)?;
+        if count == 0 {
+            return Err(invalid_data("empty run in scanline"));
+        }
+        if count > 128 {
+            let len = (count - 128) as usize;
+            let (&value, rest) = rest.split_first().ok_or_else(truncated)?;
+            let run = rgbe
+                .get_mut(x..x + len)
+                .ok_or_else(|| invalid_data("run overflows scanline"))?;
+            run.iter_mut().for_each(|p| p[c] = value);
+            x += len;
+            data = rest;
+        } else {
+            let len = count as usize;
+            let literal = rest.get(..len).ok_or_else(truncated)?;
+            let run = rgbe
+                .get_mut(x..x + len)
+                .ok_or_else(|| invalid_data("run overflows scanline"))?;
+            run.iter_mut().zip(literal).for_each(|(p, &v)| p[c] = v);
+            x += len;
+            data = &rest[len..];
+        }
+    }
+    Ok(data)
+}
+
+/// Newline terminated lines of the text header.
+struct HeaderLines<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> HeaderLines<'a> {
+    fn next(&mut self) -> io::Result<&'a [u8]> {
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid_data("unexpected end of header"))?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgbe() {
+        assert_eq!(to_rgbe(Color::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(to_rgbe(Color::new(0.0, 0.0, 0.0)), [0; 4]);
+    }
+
+    #[test]
+    fn rle() {
+        let mut out = Vec::new();
+        write_rle(&[1, 2, 3, 7, 7, 7, 7, 7, 4], &mut out).unwrap();
+        assert_eq!(out, [3, 1, 2, 3, 128 + 5, 7, 1, 4]);
+
+        let mut rgbe = [[0; 4]; 9];
+        let rest = read_rle(&out, &mut rgbe, 2).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(rgbe.map(|p| p[2]), [1, 2, 3, 7, 7, 7, 7, 7, 4]);
+    }
+
+    #[test]
+    fn rejects_sizes_the_data_cannot_hold() {
+        for resolution in ["-Y 4000000000 +X 4000000000", "-Y 1 +X 4000000000"] {
+            let data = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{resolution}\n\x02\x02");
+            let error = decode(data.as_bytes()).err().unwrap();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
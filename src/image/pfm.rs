@@ -0,0 +1,23 @@
+use std::io::{self, Write};
+
+use super::{Image, ImageEncoder};
+
+/// Portable float map: little-endian 32-bit floats, rows from the bottom up.
+#[derive(Debug, Clone, Copy)]
+pub struct PfmEncoder;
+
+impl ImageEncoder for PfmEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        // A negative scale marks the data as little-endian.
+        write!(out, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+        let bytes = image
+            .pixels()
+            .chunks(image.width() as usize)
+            .rev()
+            .flatten()
+            .flat_map(|color| [color.r, color.g, color.b])
+            .flat_map(|c| (c as f32).to_le_bytes())
+            .collect::<Vec<_>>();
+        out.write_all(&bytes)
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::{self, Write};
+
+use super::{Image, ImageEncoder, invalid_data};
+use crate::color::{Color, srgb_to_linear};
+
+/// RGB PNG with either 8 or 16 bits per channel.
+#[derive(Debug, Clone, Copy)]
+pub enum PngEncoder {
+    Rgb8,
+    Rgb16,
+}
+
+impl ImageEncoder for PngEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        let (depth, data) = match self {
+            Self::Rgb8 => (
+                ::png::BitDepth::Eight,
+                image
+                    .pixels()
+                    .iter()
+                    .flat_map(|color| {
+                        let color = color.to_int();
+                        [color.r, color.g, color.b]
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            Self::Rgb16 => (
+                ::png::BitDepth::Sixteen,
+                image
+                    .pixels()
+                    .iter()
+                    .flat_map(|color| {
+                        let color = color.to_u16();
+                        [color.r, color.g, color.b]
+                    })
+                    .flat_map(u16::to_be_bytes)
+                    .collect::<Vec<_>>(),
+            ),
+        };
+
+        let mut encoder = ::png::Encoder::new(out, image.width(), image.height());
+        encoder.set_color(::png::ColorType::Rgb);
+        encoder.set_depth(depth);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+/// Reads any PNG, dropping alpha and expanding palettes and greyscale.
+pub(super) fn decode(data: &[u8]) -> io::Result<Image> {
+    let mut decoder = ::png::Decoder::new(io::Cursor::new(data));
+    decoder.set_transformations(::png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(invalid_data_error)?;
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| invalid_data("image is too large"))?;
+    let mut buf = vec![0; size];
+    let info = reader.next_frame(&mut buf).map_err(invalid_data_error)?;
+
+    let channels = info.color_type.samples();
+    let samples = match info.bit_depth {
+        ::png::BitDepth::Sixteen => buf[..info.buffer_size()]
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / 65535.0)
+            .collect::<Vec<_>>(),
+        _ => buf[..info.buffer_size()]
+            .iter()
+            .map(|&b| b as f64 / 255.0)
+            .collect(),
+    };
+    // Greyscale has one or two channels, color three or four.
+    let color_channels = if channels < 3 { 1 } else { 3 };
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|c| {
+            let [r, g, b] =
+                [c[0], c[color_channels / 2], c[color_channels - 1]].map(srgb_to_linear);
+            Color::new(r, g, b)
+        })
+        .collect();
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn invalid_data_error(e: ::png::DecodingError) -> io::Error {
+    match e {
+        ::png::DecodingError::IoError(e) => e,
+        e => invalid_data(e.to_string()),
+    }
+}
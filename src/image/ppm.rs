@@ -0,0 +1,149 @@
+use std::io::{self, Write};
+
+use super::{Image, ImageEncoder, invalid_data};
+use crate::color::{Color, srgb_to_linear};
+
+/// Netpbm color image with 8 bits per channel.
+#[derive(Debug, Clone, Copy)]
+pub enum PpmEncoder {
+    /// Plain text P3, one pixel per line.
+    Ascii,
+    /// Raw binary P6.
+    Binary,
+}
+
+impl ImageEncoder for PpmEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> io::Result<()> {
+        let magic = match self {
+            Self::Ascii => "P3",
+            Self::Binary => "P6",
+        };
+        writeln!(out, "{magic}")?;
+        writeln!(out, "{} {}", image.width(), image.height())?;
+        writeln!(out, "255")?;
+
+        match self {
+            Self::Ascii => {
+                for color in image.pixels() {
+                    writeln!(out, "{}", color.to_int())?;
+                }
+            }
+            Self::Binary => {
+                let bytes = image
+                    .pixels()
+                    .iter()
+                    .flat_map(|color| {
+                        let color = color.to_int();
+                        [color.r, color.g, color.b]
+                    })
+                    .collect::<Vec<_>>();
+                out.write_all(&bytes)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads a plain or raw Netpbm color (P3, P6) or greyscale (P2, P5) image.
+pub(super) fn decode(data: &[u8]) -> io::Result<Image> {
+    let mut header = Header { data, pos: 0 };
+    let magic = header.token()?;
+    let (channels, raw) = match magic {
+        b"P2" => (1, false),
+        b"P3" => (3, false),
+        b"P5" => (1, true),
+        b"P6" => (3, true),
+        _ => return Err(invalid_data("not a PPM or PGM image")),
+    };
+    let width = header.number()?;
+    let height = header.number()?;
+    let max = header.number()?;
+    if max == 0 || max > 65535 {
+        return Err(invalid_data(format!("invalid maximum value {max}")));
+    }
+
+    let count = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|n| n.checked_mul(channels))
+        .ok_or_else(|| invalid_data("image is too large"))?;
+    // Every sample takes at least a byte, so a size the data can't hold is
+    // caught before allocating for it.
+    let size = if raw && max >= 256 { 2 } else { 1 };
+    if count.saturating_mul(size) > data.len() - header.pos {
+        return Err(invalid_data("image data is truncated"));
+    }
+    let samples = if raw {
+        // Exactly one whitespace byte separates the header from the data.
+        let start = header.pos + 1;
+        let bytes = data
+            .get(start..start + count * size)
+            .ok_or_else(|| invalid_data("image data is truncated"))?;
+        if size == 1 {
+            bytes.iter().map(|&b| b as u32).collect::<Vec<_>>()
+        } else {
+            bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+                .collect()
+        }
+    } else {
+        (0..count)
+            .map(|_| header.number())
+            .collect::<io::Result<Vec<_>>>()?
+    };
+
+    let scale = 1.0 / max as f64;
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|c| {
+            let [r, g, b] = [c[0], c[channels / 2], c[channels - 1]]
+                .map(|v| srgb_to_linear((v.min(max) as f64 * scale).min(1.0)));
+            Color::new(r, g, b)
+        })
+        .collect();
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Whitespace separated tokens of a Netpbm header, skipping comments.
+struct Header<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Header<'a> {
+    fn token(&mut self) -> io::Result<&'a [u8]> {
+        loop {
+            match self.data.get(self.pos) {
+                Some(b'#') => {
+                    while self.data.get(self.pos).is_some_and(|&b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                Some(_) => break,
+                None => return Err(invalid_data("unexpected end of image")),
+            }
+        }
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        Ok(&self.data[start..self.pos])
+    }
+
+    fn number(&mut self) -> io::Result<u32> {
+        let token = self.token()?;
+        std::str::from_utf8(token)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| invalid_data(format!("invalid number `{}`", token.escape_ascii())))
+    }
+}
@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{HitRecord, HitTarget},
+    interval::Interval,
+    light::{Light, LightSample},
+    ray::{Cone, Ray},
+    sampler::Sampler,
+    transform::{Decomposed, Transform},
+    vec3::Vec3,
+};
+
+/// Places a possibly shared object in the scene with a transform, which may
+/// change over time.
+pub struct Instance {
+    object: Arc<dyn HitTarget>,
+    /// Object to world space at increasing times, held before the first and
+    /// after the last and blended in between.
+    keyframes: Vec<(f64, Transform)>,
+    /// Each keyframe split up for blending.
+    parts: Vec<Decomposed>,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn HitTarget>, transform: Transform) -> Self {
+        Self::keyframed(object, vec![(0.0, transform)])
+    }
+
+    /// # Panics
+    ///
+    /// If there are no keyframes or their times don't increase.
+    pub fn keyframed(object: Arc<dyn HitTarget>, keyframes: Vec<(f64, Transform)>) -> Self {
+        assert!(!keyframes.is_empty(), "an instance needs a transform");
+        assert!(
+            keyframes.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "keyframe times must increase"
+        );
+        let parts = keyframes
+            .iter()
+            .map(|(_, transform)| transform.decompose())
+            .collect::<Vec<_>>();
+        // Turning keyframes sweep points along arcs, which can leave the
+        // keyframes' own boxes.
+        let object_bbox = object.bounding_box();
+        let bbox = keyframes.iter().fold(Aabb::EMPTY, |acc, (_, transform)| {
+            acc.union(transform.bounding_box(&object_bbox))
+        });
+        let bbox = parts.windows(2).fold(bbox, |acc, pair| {
+            acc.union(pair[0].motion_bounds(&pair[1], &object_bbox))
+        });
+        Self {
+            object,
+            keyframes,
+            parts,
+            bbox,
+        }
+    }
+
+    fn transform_at(&self, time: f64) -> Transform {
+        let keyframes = &self.keyframes;
+        let next = keyframes.partition_point(|&(t, _)| t <= time);
+        if next == 0 {
+            return keyframes[0].1;
+        }
+        let (t0, a) = keyframes[next - 1];
+        let Some(&(t1, b)) = keyframes.get(next) else {
+            return a;
+        };
+        let t = (time - t0) / (t1 - t0);
+        // Where the blend flattens the object there's nothing to hit in
+        // between, so the nearer keyframe stands in for it.
+        self.parts[next - 1]
+            .blend(&self.parts[next], t)
+            .unwrap_or(if t < 0.5 { a } else { b })
+    }
+
+    /// Transform at the time of `ray`, and the ray in object space.
+    fn object_ray(&self, ray: &Ray) -> (Transform, Ray) {
+        let transform = self.transform_at(ray.time);
+        let to_object = transform.inverse();
+        // Leaving the direction unnormalized keeps `t` the same in both spaces.
+        let object_ray = Ray {
+            origin: to_object.point(ray.origin),
+            direction: to_object.vector(ray.direction),
+            cone: Cone {
+                width: ray.cone.width / transform.scale_factor(),
+                spread: ray.cone.spread,
+            },
+            time: ray.time,
+            seed: ray.seed,
+        };
+        (transform, object_ray)
+    }
+
+    fn to_world(transform: &Transform, mut hit: HitRecord) -> HitRecord {
+        hit.point = transform.point(hit.point);
+        hit.normal = transform.normal(hit.normal).unit();
+        hit
+    }
+}
+
+impl HitTarget for Instance {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let (transform, object_ray) = self.object_ray(ray);
+        let hit = self.object.hit(&object_ray, ray_t)?;
+        Some(Self::to_world(&transform, hit))
+    }
+
+    fn hit_surface(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let (transform, object_ray) = self.object_ray(ray);
+        let hit = self.object.hit_surface(&object_ray, ray_t)?;
+        Some(Self::to_world(&transform, hit))
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f64 {
+        let (_, object_ray) = self.object_ray(ray);
+        self.object.transmittance(&object_ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        // Solid angle densities only carry over unchanged when angles are
+        // preserved, and lights are sampled as if they stood still. Other
+        // lights are still found by scattering.
+        let [(_, transform)] = self.keyframes[..] else {
+            return;
+        };
+        if !transform.is_similarity() {
+            return;
+        }
+        let mut object_lights = Vec::new();
+        self.object.lights(&mut object_lights);
+        lights.extend(
+            object_lights
+                .into_iter()
+                .map(|light| Arc::new(InstanceLight { light, transform }) as Arc<dyn Light>),
+        );
+    }
+}
+
+/// A light inside an [`Instance`].
+struct InstanceLight {
+    light: Arc<dyn Light>,
+    transform: Transform,
+}
+
+impl Light for InstanceLight {
+    fn sample(&self, origin: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let to_object = self.transform.inverse();
+        let sample = self.light.sample(to_object.point(origin), sampler)?;
+        Some(LightSample {
+            direction: self.transform.vector(sample.direction).unit(),
+            pdf: sample.pdf,
+        })
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let to_object = self.transform.inverse();
+        self.light
+            .pdf(to_object.point(origin), to_object.vector(direction))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::LambertianMaterial, sphere::Sphere};
+
+    #[test]
+    fn hits_transformed_object() {
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let sphere = Arc::new(Sphere::new(Vec3::splat(0.0), 1.0, material));
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .then(Transform::translate(Vec3::new(0.0, 0.0, -5.0)));
+        let instance = Instance::new(sphere, transform);
+
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let ray = Ray::new(Vec3::new(-5.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = instance.hit(&ray, ray_t).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-9);
+        assert!((hit.point - Vec3::new(-2.0, 0.0, -5.0)).len() < 1e-9);
+        assert!((hit.normal - Vec3::new(-1.0, 0.0, 0.0)).len() < 1e-9);
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.x.max - 2.0).abs() < 1e-3 && (bbox.z.min + 6.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn follows_keyframes() {
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let sphere = Arc::new(Sphere::new(Vec3::splat(0.0), 1.0, material));
+        let instance = Instance::keyframed(
+            sphere,
+            vec![
+                (0.0, Transform::IDENTITY),
+                (1.0, Transform::translate(Vec3::new(0.0, 4.0, 0.0))),
+            ],
+        );
+
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let ray = Ray::new(Vec3::new(-5.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(instance.hit(&ray, ray_t).is_none());
+        let hit = instance.hit(&ray.with_time(0.5), ray_t).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9);
+        assert!(instance.hit(&ray.with_time(2.0), ray_t).is_none());
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.y.min + 1.0).abs() < 1e-3 && (bbox.y.max - 5.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn turns_through_half_a_turn() {
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let center = Vec3::new(3.0, 0.0, 0.0);
+        let sphere = Arc::new(Sphere::new(center, 1.0, material));
+        let instance = Instance::keyframed(
+            sphere,
+            vec![
+                (0.0, Transform::IDENTITY),
+                (1.0, Transform::rotate(Vec3::new(0.0, 0.0, 1.0), 180.0)),
+            ],
+        );
+
+        // Halfway through, the sphere is still whole and a quarter turn round,
+        // whichever way it went.
+        let moved = instance.transform_at(0.5).point(center);
+        assert!(moved.x.abs() < 1e-9 && (moved.y.abs() - 3.0).abs() < 1e-9);
+        let ray = Ray::new(moved + Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let hit = instance.hit(&ray.with_time(0.5), ray_t).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9);
+
+        // Neither keyframe reaches that far from the x axis.
+        let bbox = instance.bounding_box();
+        assert!(bbox.y.max >= 4.0 || bbox.y.min <= -4.0);
+    }
+}
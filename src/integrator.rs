@@ -0,0 +1,420 @@
+//! Light transport algorithms that turn camera rays into radiance.
+
+use crate::{
+    background::Background,
+    hit::{HitRecord, HitTarget},
+    interval::Interval,
+    light::{LightList, power_heuristic},
+    ray::{Cone, Ray},
+    sampler::Sampler,
+    vec3::Vec3,
+};
+
+/// Computes the radiance arriving at the camera along a ray.
+pub trait Integrator: Send + Sync {
+    /// Estimate of the radiance along `ray`, drawing every random choice
+    /// from `sampler`.
+    fn radiance(&self, tracer: &Tracer, ray: &Ray, sampler: &mut dyn Sampler) -> Vec3;
+}
+
+/// Scene state shared by every path, with the queries integrators build on.
+pub struct Tracer<'a> {
+    target: &'a dyn HitTarget,
+    lights: &'a LightList,
+    background: &'a Background,
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(
+        target: &'a dyn HitTarget,
+        lights: &'a LightList,
+        background: &'a Background,
+    ) -> Self {
+        Self {
+            target,
+            lights,
+            background,
+        }
+    }
+
+    /// First thing the ray meets, skipping hits right at its origin.
+    pub fn hit(&self, ray: &Ray) -> Option<HitRecord> {
+        self.target.hit(ray, Interval::new(0.001, f64::INFINITY))
+    }
+
+    /// Radiance arriving along a ray that escapes the scene.
+    pub fn background(&self, ray: &Ray) -> Vec3 {
+        self.background.color(ray.direction)
+    }
+
+    /// Emission at `hit` seen along `ray`. `bsdf_pdf` is the density with
+    /// which the previous bounce picked the ray's direction, or `None` if
+    /// that bounce was specular or the ray came from the camera, in which
+    /// case emission is counted in full rather than weighted against light
+    /// sampling.
+    pub fn emitted(&self, ray: &Ray, hit: &HitRecord, bsdf_pdf: Option<f64>) -> Vec3 {
+        let emitted = hit.material.emitted(ray, hit);
+        match bsdf_pdf {
+            Some(bsdf_pdf) if hit.material.is_emissive() => {
+                let light_pdf = self.lights.pdf(ray.origin, ray.direction);
+                emitted * power_heuristic(bsdf_pdf, light_pdf)
+            }
+            _ => emitted,
+        }
+    }
+
+    /// Estimate of the light reflected at `hit` from a sampled point on a
+    /// light, together with its weight against BSDF sampling.
+    pub fn sample_light(
+        &self,
+        ray: &Ray,
+        hit: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Vec3, f64)> {
+        let sample = self.lights.sample(hit.point, sampler)?;
+        if sample.pdf <= 0.0 {
+            return None;
+        }
+        let f = hit.material.eval(ray, hit, sample.direction);
+        if f.is_near_zero() {
+            return None;
+        }
+
+        let shadow_ray = Ray::new(hit.point, sample.direction)
+            .with_time(ray.time)
+            .with_seed(sampler.next_u64());
+        let light_hit = self
+            .target
+            .hit_surface(&shadow_ray, Interval::new(0.001, f64::INFINITY))?;
+        if !light_hit.material.is_emissive() {
+            return None;
+        }
+        let transmittance = self
+            .target
+            .transmittance(&shadow_ray, Interval::new(0.001, light_hit.t));
+        let radiance = light_hit.material.emitted(&shadow_ray, &light_hit) * transmittance;
+        let bsdf_pdf = hit.material.pdf(ray, hit, sample.direction);
+        let weight = power_heuristic(sample.pdf, bsdf_pdf);
+        Some((f * radiance / sample.pdf, weight))
+    }
+
+    /// Whether anything blocks `ray` within `distance`, ignoring media.
+    pub fn occluded(&self, ray: &Ray, distance: f64) -> bool {
+        let ray_t = Interval::new(0.001, distance / ray.direction.len());
+        self.target.hit_surface(ray, ray_t).is_some()
+    }
+}
+
+/// Carries the footprint and time of `ray` on to a ray scattered where it
+/// hit at `t`, as if every bounce were a flat mirror, and gives it a fresh
+/// seed.
+fn follow(ray: &Ray, t: f64, scattered: Ray, sampler: &mut dyn Sampler) -> Ray {
+    scattered
+        .with_cone(Cone {
+            width: ray.width_at(t),
+            spread: ray.cone.spread,
+        })
+        .with_time(ray.time)
+        .with_seed(sampler.next_u64())
+}
+
+/// Unidirectional path tracing, sampling lights directly at every
+/// non-specular bounce and weighting both strategies with multiple
+/// importance sampling.
+pub struct PathIntegrator {
+    max_depth: u32,
+    roulette_depth: u32,
+}
+
+impl PathIntegrator {
+    /// Path tracer that follows paths for at most `max_depth` bounces, and
+    /// after `roulette_depth` bounces ends them at random with a probability
+    /// that grows as their throughput falls.
+    pub fn new(max_depth: u32, roulette_depth: u32) -> Self {
+        Self {
+            max_depth,
+            roulette_depth,
+        }
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn radiance(&self, tracer: &Tracer, ray: &Ray, sampler: &mut dyn Sampler) -> Vec3 {
+        let mut radiance = Vec3::splat(0.0);
+        let mut throughput = Vec3::splat(1.0);
+        let mut ray = *ray;
+        let mut bsdf_pdf = None;
+        for depth in 0..self.max_depth {
+            let Some(hit) = tracer.hit(&ray) else {
+                return radiance + throughput * tracer.background(&ray);
+            };
+            radiance = radiance + throughput * tracer.emitted(&ray, &hit, bsdf_pdf);
+
+            let Some(scatter) = hit.material.scatter(&ray, &hit, sampler) else {
+                break;
+            };
+            if scatter.pdf.is_some()
+                && let Some((light, weight)) = tracer.sample_light(&ray, &hit, sampler)
+            {
+                radiance = radiance + throughput * light * weight;
+            }
+            bsdf_pdf = scatter.pdf;
+            throughput = throughput * scatter.attenuation;
+            ray = follow(&ray, hit.t, scatter.scattered, sampler);
+
+            // Paths that survive make up for the ones that don't, keeping
+            // the estimate unbiased.
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.max_component().min(1.0);
+                if sampler.get_1d() >= survival {
+                    break;
+                }
+                throughput = throughput / survival;
+            }
+        }
+        radiance
+    }
+}
+
+/// Whitted-style ray tracing: lights are sampled directly at diffuse
+/// surfaces, and only mirror and glass bounces are followed further.
+pub struct WhittedIntegrator {
+    max_depth: u32,
+}
+
+impl WhittedIntegrator {
+    pub fn new(max_depth: u32) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn radiance(&self, tracer: &Tracer, ray: &Ray, sampler: &mut dyn Sampler) -> Vec3 {
+        let mut radiance = Vec3::splat(0.0);
+        let mut throughput = Vec3::splat(1.0);
+        let mut ray = *ray;
+        for _ in 0..self.max_depth {
+            let Some(hit) = tracer.hit(&ray) else {
+                return radiance + throughput * tracer.background(&ray);
+            };
+            radiance = radiance + throughput * tracer.emitted(&ray, &hit, None);
+
+            let Some(scatter) = hit.material.scatter(&ray, &hit, sampler) else {
+                break;
+            };
+            if scatter.pdf.is_some() {
+                if let Some((light, _)) = tracer.sample_light(&ray, &hit, sampler) {
+                    radiance = radiance + throughput * light;
+                }
+                break;
+            }
+            throughput = throughput * scatter.attenuation;
+            ray = follow(&ray, hit.t, scatter.scattered, sampler);
+        }
+        radiance
+    }
+}
+
+/// Fraction of the hemisphere above the first hit that is open within
+/// `distance`, weighted by cosine. Rays that miss everything count as open.
+pub struct AmbientOcclusionIntegrator {
+    distance: f64,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(distance: f64) -> Self {
+        Self { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn radiance(&self, tracer: &Tracer, ray: &Ray, sampler: &mut dyn Sampler) -> Vec3 {
+        let Some(hit) = tracer.hit(ray) else {
+            return Vec3::splat(1.0);
+        };
+        let mut direction = hit.normal + Vec3::random_unit(sampler);
+        if direction.is_near_zero() {
+            direction = hit.normal;
+        }
+        let probe = Ray::new(hit.point, direction.unit()).with_time(ray.time);
+        if tracer.occluded(&probe, self.distance) {
+            Vec3::splat(0.0)
+        } else {
+            Vec3::splat(1.0)
+        }
+    }
+}
+
+/// Shading normals of the first hit, mapped from `[-1, 1]` to colors.
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn radiance(&self, tracer: &Tracer, ray: &Ray, _sampler: &mut dyn Sampler) -> Vec3 {
+        tracer.hit(ray).map_or(Vec3::splat(0.0), |hit| {
+            0.5 * (hit.normal + Vec3::splat(1.0))
+        })
+    }
+}
+
+/// Albedo of the first hit, or the background where rays escape.
+pub struct AlbedoIntegrator;
+
+impl Integrator for AlbedoIntegrator {
+    fn radiance(&self, tracer: &Tracer, ray: &Ray, _sampler: &mut dyn Sampler) -> Vec3 {
+        match tracer.hit(ray) {
+            Some(hit) => hit.material.albedo(&hit),
+            None => tracer.background(ray),
+        }
+    }
+}
+
+/// Rendering algorithm to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegratorKind {
+    #[default]
+    Path,
+    Whitted,
+    AmbientOcclusion,
+    Normals,
+    Albedo,
+}
+
+/// Everything needed to set up an [`Integrator`].
+#[derive(Debug, Clone)]
+pub struct IntegratorSettings {
+    pub kind: IntegratorKind,
+    /// Maximum number of bounces per path.
+    pub max_depth: u32,
+    /// Number of bounces after which path tracing starts ending paths with
+    /// Russian roulette.
+    pub roulette_depth: u32,
+    /// Distance within which ambient occlusion looks for occluders.
+    pub ao_distance: f64,
+}
+
+impl Default for IntegratorSettings {
+    fn default() -> Self {
+        Self {
+            kind: IntegratorKind::Path,
+            max_depth: 50,
+            roulette_depth: 3,
+            ao_distance: 1.0,
+        }
+    }
+}
+
+impl IntegratorSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_depth == 0 {
+            return Err("max depth must be at least 1".to_string());
+        }
+        if self.ao_distance.is_nan() || self.ao_distance <= 0.0 {
+            return Err(format!(
+                "ambient occlusion distance must be positive, got {}",
+                self.ao_distance
+            ));
+        }
+        Ok(())
+    }
+
+    /// # Panics
+    ///
+    /// If the settings don't pass [`IntegratorSettings::validate`].
+    pub fn build(&self) -> Box<dyn Integrator> {
+        if let Err(e) = self.validate() {
+            panic!("invalid integrator settings: {e}");
+        }
+        match self.kind {
+            IntegratorKind::Path => {
+                Box::new(PathIntegrator::new(self.max_depth, self.roulette_depth))
+            }
+            IntegratorKind::Whitted => Box::new(WhittedIntegrator::new(self.max_depth)),
+            IntegratorKind::AmbientOcclusion => {
+                Box::new(AmbientOcclusionIntegrator::new(self.ao_distance))
+            }
+            IntegratorKind::Normals => Box::new(NormalsIntegrator),
+            IntegratorKind::Albedo => Box::new(AlbedoIntegrator),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        hit::HitWorld,
+        material::{Isotropic, LambertianMaterial},
+        medium::ConstantMedium,
+        plane::Plane,
+        sampler::IndependentSampler,
+        sphere::Sphere,
+    };
+
+    fn render(integrator: &dyn Integrator, world: &HitWorld, ray: &Ray, samples: u32) -> Vec3 {
+        let lights = LightList::new(world);
+        let background = Background::Solid(Vec3::splat(1.0));
+        let tracer = Tracer::new(world, &lights, &background);
+        let sum = (0..samples).fold(Vec3::splat(0.0), |sum, sample| {
+            let mut sampler = IndependentSampler::new(0);
+            sampler.start((0, 0), sample);
+            let ray = ray.with_seed(sampler.next_u64());
+            sum + integrator.radiance(&tracer, &ray, &mut sampler)
+        });
+        sum / samples as f64
+    }
+
+    #[test]
+    fn path_tracing_passes_furnace_test() {
+        // A convex diffuse object under uniform light reflects its albedo.
+        let mut world = HitWorld::new();
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        world.push(Sphere::new(Vec3::splat(0.0), 1.0, material));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let path = render(&PathIntegrator::new(50, 50), &world, &ray, 100);
+        assert!((path - Vec3::splat(0.5)).len() < 1e-9, "{path:?}");
+        let albedo = render(&AlbedoIntegrator, &world, &ray, 1);
+        assert_eq!(albedo, Vec3::splat(0.5));
+        let normal = render(&NormalsIntegrator, &world, &ray, 1);
+        assert!((normal - Vec3::new(0.5, 0.5, 1.0)).len() < 1e-9);
+    }
+
+    #[test]
+    fn roulette_is_unbiased() {
+        // Light scattering many times in a dense cloud before escaping.
+        let mut world = HitWorld::new();
+        let phase = Arc::new(Isotropic::new(Vec3::splat(0.9)));
+        let boundary = Sphere::new(Vec3::splat(0.0), 1.0, phase.clone());
+        world.push(ConstantMedium::new(boundary, 4.0, phase));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let full = render(&PathIntegrator::new(200, 200), &world, &ray, 20_000);
+        let roulette = render(&PathIntegrator::new(200, 0), &world, &ray, 20_000);
+        assert!((full.x - roulette.x).abs() < 0.02, "{full:?} {roulette:?}");
+    }
+
+    #[test]
+    fn ambient_occlusion_sees_nearby_walls() {
+        let mut world = HitWorld::new();
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        world.push(Plane::new(Vec3::splat(0.0), up, material.clone()));
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), -up);
+
+        let open = render(&AmbientOcclusionIntegrator::new(1.0), &world, &ray, 100);
+        assert_eq!(open, Vec3::splat(1.0));
+
+        world.push(Plane::new(Vec3::new(0.0, 2.0, 0.0), -up, material));
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), -up);
+        let near = render(&AmbientOcclusionIntegrator::new(1.0), &world, &ray, 100);
+        let far = render(
+            &AmbientOcclusionIntegrator::new(f64::INFINITY),
+            &world,
+            &ray,
+            100,
+        );
+        assert!(near.x > 0.0 && far.x == 0.0, "{near:?} {far:?}");
+    }
+}
@@ -7,14 +7,31 @@ pub struct Interval {
 }
 
 impl Interval {
+    pub const EMPTY: Self = Self::new(f64::INFINITY, f64::NEG_INFINITY);
+    pub const UNIVERSE: Self = Self::new(f64::NEG_INFINITY, f64::INFINITY);
+
     pub const fn new(min: f64, max: f64) -> Self {
         Self { min, max }
     }
 
+    /// Smallest interval enclosing both `a` and `b`.
+    pub fn hull(a: Self, b: Self) -> Self {
+        Self::new(a.min.min(b.min), a.max.max(b.max))
+    }
+
     pub fn with_max(self, max: f64) -> Self {
         Self { min: self.min, max }
     }
 
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    pub fn expand(self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Self::new(self.min - padding, self.max + padding)
+    }
+
     pub fn surrounds(&self, x: f64) -> bool {
         self.min < x && x < self.max
     }
@@ -0,0 +1,31 @@
+pub mod aabb;
+pub mod background;
+pub mod bvh;
+pub mod camera;
+pub mod checkpoint;
+pub mod color;
+pub mod disk;
+pub mod film;
+pub mod filter;
+pub mod grid;
+pub mod hit;
+pub mod image;
+pub mod instance;
+pub mod integrator;
+pub mod interval;
+pub mod light;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod obj;
+pub mod onb;
+pub mod perlin;
+pub mod plane;
+pub mod quad;
+pub mod ray;
+pub mod sampler;
+pub mod scene;
+pub mod sphere;
+pub mod texture;
+pub mod transform;
+pub mod vec3;
@@ -0,0 +1,314 @@
+//! Explicit light sampling for next-event estimation.
+
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    disk::intersect_disk, hit::HitTarget, interval::Interval, mesh::intersect_triangle, onb::Onb,
+    quad::intersect_quad, ray::Ray, sampler::Sampler, vec3::Vec3,
+};
+
+/// Direction towards a light, chosen by [`Light::sample`].
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    pub direction: Vec3,
+    /// Solid angle density of `direction`.
+    pub pdf: f64,
+}
+
+/// Emissive geometry that can be sampled directly.
+pub trait Light: Send + Sync {
+    /// Picks a direction from `origin` towards a point on the light.
+    fn sample(&self, origin: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample>;
+
+    /// Solid angle density with which [`Light::sample`] picks `direction`
+    /// from `origin`, zero if the direction misses the light.
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f64;
+}
+
+/// Every light in a scene, sampled uniformly.
+pub struct LightList {
+    lights: Vec<Arc<dyn Light>>,
+}
+
+impl LightList {
+    /// Collects the lights of everything in `target`.
+    pub fn new(target: &dyn HitTarget) -> Self {
+        let mut lights = Vec::new();
+        target.lights(&mut lights);
+        Self { lights }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn sample(&self, origin: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let light = &self.lights[sampler.index(self.lights.len())];
+        let sample = light.sample(origin, sampler)?;
+        Some(LightSample {
+            direction: sample.direction,
+            pdf: self.pdf(origin, sample.direction),
+        })
+    }
+
+    /// Density of [`LightList::sample`] picking `direction`, accounting for
+    /// every light that could have produced it.
+    pub fn pdf(&self, origin: Vec3, direction: Vec3) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sum = self
+            .lights
+            .iter()
+            .map(|light| light.pdf(origin, direction))
+            .sum::<f64>();
+        sum / self.lights.len() as f64
+    }
+}
+
+/// Sampled by the cone of directions it subtends.
+pub struct SphereLight {
+    center: Vec3,
+    radius: f64,
+}
+
+impl SphereLight {
+    pub fn new(center: Vec3, radius: f64) -> Self {
+        Self { center, radius }
+    }
+
+    /// Cosine of the cone's half-angle, or `None` from inside the sphere.
+    fn cos_theta_max(&self, origin: Vec3) -> Option<f64> {
+        let distance_squared = (self.center - origin).len_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, origin: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let cos_theta_max = self.cos_theta_max(origin)?;
+        let (u, v) = sampler.get_2d();
+        let z = 1.0 + u * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * v;
+        let r = (1.0 - z * z).sqrt();
+        let local = Vec3::new(phi.cos() * r, phi.sin() * r, z);
+        Some(LightSample {
+            direction: Onb::new(self.center - origin).local(local),
+            pdf: 1.0 / (2.0 * PI * (1.0 - cos_theta_max)),
+        })
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return 0.0;
+        };
+        if direction.unit().dot((self.center - origin).unit()) < cos_theta_max {
+            return 0.0;
+        }
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+}
+
+/// Sampled uniformly by area.
+pub struct TriangleLight {
+    vertices: [Vec3; 3],
+    normal: Vec3,
+    area: f64,
+}
+
+impl TriangleLight {
+    pub fn new(vertices: [Vec3; 3]) -> Self {
+        let [p0, p1, p2] = vertices;
+        let cross = (p1 - p0).cross(p2 - p0);
+        Self {
+            vertices,
+            normal: cross.unit(),
+            area: 0.5 * cross.len(),
+        }
+    }
+}
+
+impl Light for TriangleLight {
+    fn sample(&self, origin: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let (u, v) = sampler.get_2d();
+        let su = u.sqrt();
+        let b0 = 1.0 - su;
+        let b1 = v * su;
+        let [p0, p1, p2] = self.vertices;
+        let point = b0 * p0 + b1 * p1 + (1.0 - b0 - b1) * p2;
+        area_sample(origin, point, self.normal, self.area)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let [p0, p1, p2] = self.vertices;
+        let ray = Ray::new(origin, direction);
+        match intersect_triangle(&ray, Interval::new(0.0, f64::INFINITY), p0, p1, p2) {
+            Some((t, _)) => solid_angle_pdf(direction, t, self.normal, self.area),
+            None => 0.0,
+        }
+    }
+}
+
+/// Sampled uniformly by area.
+pub struct QuadLight {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    area: f64,
+}
+
+impl QuadLight {
+    pub fn new(origin: Vec3, u: Vec3, v: Vec3) -> Self {
+        let cross = u.cross(v);
+        Self {
+            origin,
+            u,
+            v,
+            normal: cross.unit(),
+            area: cross.len(),
+        }
+    }
+}
+
+impl Light for QuadLight {
+    fn sample(&self, origin: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let (u, v) = sampler.get_2d();
+        let point = self.origin + u * self.u + v * self.v;
+        area_sample(origin, point, self.normal, self.area)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let ray = Ray::new(origin, direction);
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        match intersect_quad(&ray, ray_t, self.origin, self.u, self.v) {
+            Some((t, _, _)) => solid_angle_pdf(direction, t, self.normal, self.area),
+            None => 0.0,
+        }
+    }
+}
+
+/// Sampled uniformly by area.
+pub struct DiskLight {
+    center: Vec3,
+    radius: f64,
+    basis: Onb,
+}
+
+impl DiskLight {
+    pub fn new(center: Vec3, normal: Vec3, radius: f64) -> Self {
+        Self {
+            center,
+            radius,
+            basis: Onb::new(normal),
+        }
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+}
+
+impl Light for DiskLight {
+    fn sample(&self, origin: Vec3, sampler: &mut dyn Sampler) -> Option<LightSample> {
+        let p = Vec3::random_in_unit_disk(sampler) * self.radius;
+        let point = self.center + self.basis.local(p);
+        area_sample(origin, point, self.basis.w, self.area())
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> f64 {
+        let ray = Ray::new(origin, direction);
+        let ray_t = Interval::new(0.0, f64::INFINITY);
+        match intersect_disk(&ray, ray_t, self.center, self.basis.w, self.radius) {
+            Some(t) => solid_angle_pdf(direction, t, self.basis.w, self.area()),
+            None => 0.0,
+        }
+    }
+}
+
+/// Direction from `origin` to `point`, sampled uniformly from a flat light of
+/// the given area.
+fn area_sample(origin: Vec3, point: Vec3, normal: Vec3, area: f64) -> Option<LightSample> {
+    let direction = point - origin;
+    let pdf = solid_angle_pdf(direction, 1.0, normal, area);
+    (pdf > 0.0).then(|| LightSample {
+        direction: direction.unit(),
+        pdf,
+    })
+}
+
+/// Converts a uniform density over a flat light's area to solid angle, as
+/// seen along `direction` from a distance of `t` times its length.
+fn solid_angle_pdf(direction: Vec3, t: f64, normal: Vec3, area: f64) -> f64 {
+    let distance_squared = t * t * direction.len_squared();
+    let cosine = (direction.dot(normal) / direction.len()).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+/// Weight of a sample taken with density `pdf` when another strategy could
+/// have produced it with density `other_pdf`.
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let a = pdf * pdf;
+    let b = other_pdf * other_pdf;
+    if a + b == 0.0 { 0.0 } else { a / (a + b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    fn assert_consistent(light: &dyn Light, origin: Vec3) {
+        let mut sampler = IndependentSampler::new(0);
+        for _ in 0..100 {
+            let sample = light.sample(origin, &mut sampler).unwrap();
+            let pdf = light.pdf(origin, sample.direction);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-6 * pdf,
+                "{pdf} != {}",
+                sample.pdf
+            );
+        }
+    }
+
+    #[test]
+    fn sphere_pdf_matches_samples() {
+        let light = SphereLight::new(Vec3::new(0.0, 5.0, 0.0), 1.0);
+        assert_consistent(&light, Vec3::splat(0.0));
+        assert_eq!(light.pdf(Vec3::splat(0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn triangle_pdf_matches_samples() {
+        let light = TriangleLight::new([
+            Vec3::new(-1.0, 2.0, -1.0),
+            Vec3::new(1.0, 2.0, -1.0),
+            Vec3::new(0.0, 2.0, 1.0),
+        ]);
+        assert_consistent(&light, Vec3::new(0.3, 0.0, 0.2));
+        assert_eq!(light.pdf(Vec3::splat(0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn flat_light_pdfs_match_samples() {
+        let light = QuadLight::new(
+            Vec3::new(-1.0, 2.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.5, 2.0),
+        );
+        assert_consistent(&light, Vec3::new(0.3, 0.0, 0.2));
+        let light = DiskLight::new(Vec3::new(0.0, 3.0, 0.0), Vec3::new(0.2, -1.0, 0.0), 0.5);
+        assert_consistent(&light, Vec3::new(0.3, 0.0, 0.2));
+        assert_eq!(light.pdf(Vec3::splat(0.0), Vec3::new(1.0, 0.0, 0.0)), 0.0);
+    }
+}
@@ -1,93 +1,467 @@
-use std::sync::Arc;
-
-use rand::{Rng, rng};
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
 
-use crate::{
+use clap::{ArgGroup, CommandFactory, Parser, ValueEnum, error::ErrorKind};
+use rt::{
     camera::Camera,
-    hit::HitWorld,
-    material::{DielectricMaterial, LambertianMaterial, Material, MetalMaterial},
-    sphere::Sphere,
-    vec3::Vec3,
+    checkpoint::Checkpoint,
+    film::Film,
+    filter::FilterKind,
+    image::{ExrCompression, ExrEncoder, ExrPrecision, ImageFormat},
+    integrator::{IntegratorKind, Tracer},
+    light::LightList,
+    sampler::SamplerKind,
+    scene,
 };
 
-mod camera;
-mod color;
-mod hit;
-mod interval;
-mod material;
-mod ray;
-mod sphere;
-mod vec3;
-
-fn main() {
-    let mut world = HitWorld::new();
-    let ground_material = Arc::new(LambertianMaterial::new(Vec3::new(0.5, 0.5, 0.5)));
-    world.push(Sphere::new(
-        Vec3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    ));
-
-    let mut rng = rng();
-    for a in -11..11 {
-        for b in -11..11 {
-            let choose_mat = rng.random::<f64>();
-            let center = Vec3::new(
-                a as f64 + 0.9 * rng.random::<f64>(),
-                0.2,
-                b as f64 + 0.9 * rng.random::<f64>(),
-            );
-
-            if (center - Vec3::new(4.0, 0.2, 0.0)).len() > 0.9 {
-                let sphere_material: Arc<dyn Material> = if choose_mat < 0.8 {
-                    // diffuse
-                    let albedo = Vec3::random() * Vec3::random();
-                    Arc::new(LambertianMaterial::new(albedo))
-                } else if choose_mat < 0.95 {
-                    // metal
-                    let albedo = Vec3::random_range(0.5, 1.0);
-                    let fuzz = rng.random_range(0.0..0.5);
-                    Arc::new(MetalMaterial::new(albedo, fuzz))
-                } else {
-                    // glass
-                    Arc::new(DielectricMaterial::new(1.5))
-                };
-                world.push(Sphere::new(center, 0.2, sphere_material));
-            }
+/// Renders a scene description with a path tracer.
+#[derive(Parser)]
+#[command(version, group(ArgGroup::new("updates").multiple(true)))]
+struct Args {
+    /// Scene description file
+    scene: PathBuf,
+
+    /// Image width in pixels
+    #[arg(short = 'W', long)]
+    width: Option<u32>,
+
+    /// Image height in pixels, overriding the aspect ratio
+    #[arg(short = 'H', long)]
+    height: Option<u32>,
+
+    /// Image aspect ratio, either a number or a ratio like 16/9
+    #[arg(long, value_parser = parse_ratio)]
+    aspect_ratio: Option<f64>,
+
+    /// Samples per pixel, or the most per pixel with adaptive sampling
+    #[arg(short, long)]
+    samples: Option<u16>,
+
+    /// Fewest samples per pixel with adaptive sampling
+    #[arg(long)]
+    min_samples: Option<u16>,
+
+    /// Noise level at which pixels stop taking samples, enabling adaptive sampling
+    #[arg(long)]
+    adaptive_threshold: Option<f64>,
+
+    /// Maximum number of bounces per path
+    #[arg(short = 'd', long)]
+    max_depth: Option<u32>,
+
+    /// Bounces after which paths may be ended early by Russian roulette
+    #[arg(long)]
+    roulette_depth: Option<u32>,
+
+    /// Rendering algorithm [default: from the scene, or path]
+    #[arg(short, long, value_enum)]
+    integrator: Option<Integrator>,
+
+    /// Vertical field of view in degrees
+    #[arg(long)]
+    vfov: Option<f64>,
+
+    /// Defocus blur angle in degrees
+    #[arg(long)]
+    defocus_angle: Option<f64>,
+
+    /// Seed for the random numbers of every sample
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// How samples are spread within each pixel [default: from the scene, or sobol]
+    #[arg(long, value_enum)]
+    sampler: Option<Sampler>,
+
+    /// Reconstruction filter [default: from the scene, or box]
+    #[arg(long, value_enum)]
+    filter: Option<Filter>,
+
+    /// Filter radius in pixels [default: from the scene, or the filter's own]
+    #[arg(long)]
+    filter_radius: Option<f64>,
+
+    /// Number of render threads, 0 for one per core
+    #[arg(short = 'j', long, default_value_t = 0)]
+    threads: usize,
+
+    /// Output image path, or standard output if omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Render in passes of doubling samples per pixel, rewriting the output
+    /// image after each one
+    #[arg(long, requires = "output", group = "updates")]
+    progressive: bool,
+
+    /// Save the render's progress here after each pass, so that it can be
+    /// resumed if interrupted or continued with more samples
+    #[arg(long, group = "updates")]
+    checkpoint: Option<PathBuf>,
+
+    /// Carry on the render saved in this checkpoint, keeping its seed,
+    /// sampler and filter
+    #[arg(long)]
+    resume: Option<PathBuf>,
+
+    /// With --progressive or --checkpoint, rewrite the output image and
+    /// checkpoint every this many seconds instead of after each pass
+    #[arg(long, requires = "updates", value_parser = parse_seconds)]
+    update_interval: Option<Duration>,
+
+    /// Also write the number of samples taken in each pixel as an image here
+    #[arg(long)]
+    heatmap: Option<PathBuf>,
+
+    /// Output image format [default: from the output extension, or ppm]
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+
+    /// Compression for OpenEXR output
+    #[arg(long, value_enum, default_value_t = Compression::Zip)]
+    exr_compression: Compression,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// ASCII PPM (P3)
+    PpmAscii,
+    /// Binary PPM (P6)
+    Ppm,
+    /// 8-bit PNG
+    Png,
+    /// 16-bit PNG
+    Png16,
+    /// Radiance RGBE
+    Hdr,
+    /// Portable float map
+    Pfm,
+    /// OpenEXR with 16-bit floats
+    Exr,
+    /// OpenEXR with 32-bit floats
+    ExrFloat,
+}
+
+impl From<Format> for ImageFormat {
+    fn from(format: Format) -> Self {
+        let exr = |precision| {
+            Self::Exr(ExrEncoder {
+                precision,
+                ..Default::default()
+            })
+        };
+        match format {
+            Format::PpmAscii => Self::PpmAscii,
+            Format::Ppm => Self::Ppm,
+            Format::Png => Self::Png,
+            Format::Png16 => Self::Png16,
+            Format::Hdr => Self::Hdr,
+            Format::Pfm => Self::Pfm,
+            Format::Exr => exr(ExrPrecision::Half),
+            Format::ExrFloat => exr(ExrPrecision::Float),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Integrator {
+    /// Path tracing with light sampling
+    Path,
+    /// Direct light plus mirror and glass reflections
+    Whitted,
+    /// Ambient occlusion
+    Ao,
+    /// Surface normals as colors
+    Normals,
+    /// Surface albedo
+    Albedo,
+}
+
+impl From<Integrator> for IntegratorKind {
+    fn from(integrator: Integrator) -> Self {
+        match integrator {
+            Integrator::Path => Self::Path,
+            Integrator::Whitted => Self::Whitted,
+            Integrator::Ao => Self::AmbientOcclusion,
+            Integrator::Normals => Self::Normals,
+            Integrator::Albedo => Self::Albedo,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sampler {
+    /// Independent random numbers
+    Independent,
+    /// Jittered strata
+    Stratified,
+    /// Owen-scrambled Halton sequence
+    Halton,
+    /// Owen-scrambled Sobol sequence
+    Sobol,
+}
+
+impl From<Sampler> for SamplerKind {
+    fn from(sampler: Sampler) -> Self {
+        match sampler {
+            Sampler::Independent => Self::Independent,
+            Sampler::Stratified => Self::Stratified,
+            Sampler::Halton => Self::Halton,
+            Sampler::Sobol => Self::Sobol,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Filter {
+    /// Plain average of the samples in each pixel
+    Box,
+    /// Linear falloff
+    Tent,
+    /// Gaussian falloff
+    Gaussian,
+    /// Mitchell-Netravali cubic, slightly sharpening
+    Mitchell,
+    /// Windowed sinc, sharpening
+    Lanczos,
+}
+
+impl From<Filter> for FilterKind {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Box => Self::Box,
+            Filter::Tent => Self::Tent,
+            Filter::Gaussian => Self::Gaussian,
+            Filter::Mitchell => Self::Mitchell,
+            Filter::Lanczos => Self::Lanczos,
         }
     }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Compression {
+    None,
+    Rle,
+    Zip,
+    Piz,
+}
+
+impl From<Compression> for ExrCompression {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => Self::None,
+            Compression::Rle => Self::Rle,
+            Compression::Zip => Self::Zip,
+            Compression::Piz => Self::Piz,
+        }
+    }
+}
+
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    let ratio = match s.split_once('/') {
+        Some((n, d)) => n
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .zip(d.trim().parse::<f64>().ok())
+            .map(|(n, d)| n / d),
+        None => s.parse().ok(),
+    };
+    ratio.ok_or_else(|| format!("`{s}` is not a number or ratio"))
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("`{s}` is not a number of seconds"))
+}
+
+/// Saves a file next to `path` with `save` and renames it over `path`, so
+/// that anything reading `path` sees either the old contents or the new.
+fn replace_file(path: &Path, save: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    save(&temp)?;
+    fs::rename(&temp, path)
+}
 
-    let material1 = Arc::new(DielectricMaterial::new(1.5));
-    world.push(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, material1));
-
-    let material2 = Arc::new(LambertianMaterial::new(Vec3::new(0.4, 0.2, 0.1)));
-    world.push(Sphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0, material2));
-
-    let material3 = Arc::new(MetalMaterial::new(Vec3::new(0.7, 0.6, 0.5), 0.0));
-    world.push(Sphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0, material3));
-
-    let aspect_ratio = 16.0 / 9.0;
-    let image_width = 1200;
-    let samples_per_pixel = 500;
-    let max_depth = 50;
-    let vfov = 20.0;
-    let lookfrom = Vec3::new(13.0, 2.0, 3.0);
-    let lookat = Vec3::new(0.0, 0.0, 0.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
-    let defocus_angle = 0.6;
-    let focus_dist = 10.0;
-
-    let camera = Camera::new(
-        image_width,
-        aspect_ratio,
-        samples_per_pixel,
-        max_depth,
-        vfov,
-        lookfrom,
-        lookat,
-        vup,
-        defocus_angle,
-        focus_dist,
-    );
-    camera.render(&world);
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let mut scene = match scene::load_scene(&args.scene) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let settings = &mut scene.camera;
+    if let Some(width) = args.width {
+        settings.image_width = width;
+    }
+    if let Some(aspect_ratio) = args.aspect_ratio {
+        settings.aspect_ratio = aspect_ratio;
+    }
+    if let Some(height) = args.height {
+        settings.image_height = Some(height);
+    }
+    if let Some(samples) = args.samples {
+        settings.samples_per_pixel = samples;
+    }
+    if let Some(min_samples) = args.min_samples {
+        settings.min_samples = min_samples;
+    }
+    if let Some(threshold) = args.adaptive_threshold {
+        settings.adaptive_threshold = Some(threshold);
+    }
+    if let Some(vfov) = args.vfov {
+        settings.vfov = vfov;
+    }
+    if let Some(defocus_angle) = args.defocus_angle {
+        settings.defocus_angle = defocus_angle;
+    }
+    if let Some(seed) = args.seed {
+        settings.seed = seed;
+    }
+    if let Some(sampler) = args.sampler {
+        settings.sampler = sampler.into();
+    }
+    if let Some(filter) = args.filter {
+        settings.filter = filter.into();
+    }
+    if let Some(radius) = args.filter_radius {
+        settings.filter_radius = Some(radius);
+    }
+    if let Err(e) = settings.validate() {
+        Args::command().error(ErrorKind::ValueValidation, e).exit();
+    }
+
+    let integrator = &mut scene.integrator;
+    if let Some(max_depth) = args.max_depth {
+        integrator.max_depth = max_depth;
+    }
+    if let Some(roulette_depth) = args.roulette_depth {
+        integrator.roulette_depth = roulette_depth;
+    }
+    if let Some(kind) = args.integrator {
+        integrator.kind = kind.into();
+    }
+    if let Err(e) = integrator.validate() {
+        Args::command().error(ErrorKind::ValueValidation, e).exit();
+    }
+
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()
+    {
+        eprintln!("error: failed to start render threads: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut camera = Camera::new(&scene.camera);
+    let resumed = match &args.resume {
+        Some(path) => match Checkpoint::load(path)
+            .map_err(|e| e.to_string())
+            .and_then(|checkpoint| camera.resume(checkpoint))
+        {
+            Ok(film) => Some(film),
+            Err(e) => {
+                eprintln!("error: {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    // Progressive renders replace the output file as they go instead of
+    // writing into it.
+    let out: Option<Box<dyn Write>> = match &args.output {
+        _ if args.progressive => None,
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(Box::new(BufWriter::new(file))),
+            Err(e) => {
+                eprintln!("error: {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Some(Box::new(BufWriter::new(io::stdout().lock()))),
+    };
+
+    let mut format = args
+        .format
+        .map(ImageFormat::from)
+        .or_else(|| args.output.as_deref().and_then(ImageFormat::from_path))
+        .unwrap_or(ImageFormat::Ppm);
+    if let ImageFormat::Exr(encoder) = &mut format {
+        encoder.compression = args.exr_compression.into();
+    }
+
+    let integrator = scene.integrator.build();
+    let world = scene.world.into_bvh();
+    let lights = LightList::new(&world);
+    let tracer = Tracer::new(&world, &lights, &scene.background);
+    let save_checkpoint = |film: &Film| {
+        args.checkpoint.as_deref().map_or(Ok(()), |path| {
+            replace_file(path, |temp| camera.checkpoint(film).save(temp))
+        })
+    };
+    // Checkpointed renders go in passes too, so that an interrupted render
+    // leaves an evenly sampled image.
+    let film = if args.progressive || args.checkpoint.is_some() || resumed.is_some() {
+        let film = resumed
+            .unwrap_or_else(|| Film::new(scene.camera.image_width, scene.camera.image_height()));
+        let update = &mut |film: &Film| {
+            if let (None, Some(path)) = (&out, &args.output)
+                && let Err(e) = replace_file(path, |temp| film.image().save(temp, format))
+            {
+                eprintln!("error: failed to update image: {e}");
+            }
+            if let Err(e) = save_checkpoint(film) {
+                eprintln!("error: failed to save checkpoint: {e}");
+            }
+        };
+        camera.render_progressive(
+            integrator.as_ref(),
+            &tracer,
+            film,
+            args.update_interval,
+            update,
+        )
+    } else {
+        camera.render(integrator.as_ref(), &tracer)
+    };
+    if let Err(e) = save_checkpoint(&film) {
+        eprintln!("error: failed to save checkpoint: {e}");
+        return ExitCode::FAILURE;
+    }
+    let written = match (out, &args.output) {
+        (Some(mut out), _) => film
+            .image()
+            .write(format, &mut out)
+            .and_then(|()| out.flush()),
+        (None, Some(path)) => replace_file(path, |temp| film.image().save(temp, format)),
+        (None, None) => unreachable!("progressive renders have an output path"),
+    };
+    if let Err(e) = written {
+        eprintln!("error: failed to write image: {e}");
+        return ExitCode::FAILURE;
+    }
+    if let Some(path) = &args.heatmap {
+        let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Png);
+        if let Err(e) = film.heatmap().save(path, format) {
+            eprintln!("error: {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
 }
@@ -1,68 +1,135 @@
-use rand::{Rng, rng};
+use std::{f64::consts::PI, sync::Arc};
 
-use crate::{hit::HitRecord, ray::Ray, vec3::Vec3};
+use crate::{
+    hit::HitRecord,
+    onb::Onb,
+    ray::Ray,
+    sampler::Sampler,
+    texture::{SolidColor, Texture},
+    vec3::Vec3,
+};
 
 pub struct Scatter {
+    /// BSDF times cosine over `pdf`.
     pub attenuation: Vec3,
     pub scattered: Ray,
+    /// Solid angle density of the scattered direction, or `None` for
+    /// specular scattering that light sampling can't contribute to.
+    pub pdf: Option<f64>,
 }
 
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<Scatter>;
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter>;
+
+    /// BSDF times cosine, or the phase function inside a medium, for light
+    /// arriving from `direction` and leaving towards the origin of `ray`.
+    /// Specular materials return zero.
+    fn eval(&self, _ray: &Ray, _hit: &HitRecord, _direction: Vec3) -> Vec3 {
+        Vec3::splat(0.0)
+    }
+
+    /// Solid angle density with which [`Material::scatter`] picks `direction`.
+    fn pdf(&self, _ray: &Ray, _hit: &HitRecord, _direction: Vec3) -> f64 {
+        0.0
+    }
+
+    /// Overall color of the surface at `hit`, for debugging.
+    fn albedo(&self, _hit: &HitRecord) -> Vec3 {
+        Vec3::splat(0.0)
+    }
+
+    /// Light given off by the surface towards the origin of `ray`.
+    fn emitted(&self, _ray: &Ray, _hit: &HitRecord) -> Vec3 {
+        Vec3::splat(0.0)
+    }
+
+    /// Whether the material's emission is sampled as a light. Light from
+    /// other materials is only found by scattering.
+    fn is_emissive(&self) -> bool {
+        false
+    }
 }
 
 pub struct LambertianMaterial {
-    albedo: Vec3,
+    albedo: Arc<dyn Texture>,
 }
 
 impl LambertianMaterial {
     pub fn new(albedo: Vec3) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 }
 
 impl Material for LambertianMaterial {
-    fn scatter(&self, _ray: &Ray, hit: &HitRecord) -> Option<Scatter> {
-        let mut scatter_dir = hit.normal + Vec3::random_unit();
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let mut scatter_dir = hit.normal + Vec3::random_unit(sampler);
         if scatter_dir.is_near_zero() {
             scatter_dir = hit.normal;
         }
 
         let scattered = Ray::new(hit.point, scatter_dir);
-        let attenuation = self.albedo;
+        let attenuation = self.albedo.value(hit);
+        let pdf = self.pdf(ray, hit, scatter_dir);
         Some(Scatter {
             attenuation,
             scattered,
+            pdf: Some(pdf),
         })
     }
+
+    fn eval(&self, _ray: &Ray, hit: &HitRecord, direction: Vec3) -> Vec3 {
+        let cosine = hit.normal.dot(direction.unit()).max(0.0);
+        self.albedo.value(hit) * (cosine / PI)
+    }
+
+    fn pdf(&self, _ray: &Ray, hit: &HitRecord, direction: Vec3) -> f64 {
+        hit.normal.dot(direction.unit()).max(0.0) / PI
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Vec3 {
+        self.albedo.value(hit)
+    }
 }
 
 pub struct MetalMaterial {
-    albedo: Vec3,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl MetalMaterial {
     pub fn new(albedo: Vec3, fuzz: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self { albedo, fuzz }
     }
 }
 
 impl Material for MetalMaterial {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<Scatter> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
         let reflected = ray.direction.reflect(hit.normal);
-        let reflected = reflected.unit() + self.fuzz * Vec3::random_unit();
+        let reflected = reflected.unit() + self.fuzz * Vec3::random_unit(sampler);
         let scattered = Ray::new(hit.point, reflected);
-        let attenuation = self.albedo;
+        let attenuation = self.albedo.value(hit);
         if scattered.direction.dot(hit.normal) > 0.0 {
             Some(Scatter {
                 attenuation,
                 scattered,
+                pdf: None,
             })
         } else {
             None
         }
     }
+
+    fn albedo(&self, hit: &HitRecord) -> Vec3 {
+        self.albedo.value(hit)
+    }
 }
 
 pub struct DielectricMaterial {
@@ -82,7 +149,7 @@ impl DielectricMaterial {
 }
 
 impl Material for DielectricMaterial {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<Scatter> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
         let ri = if hit.front_face {
             1.0 / self.refraction_index
         } else {
@@ -94,7 +161,7 @@ impl Material for DielectricMaterial {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction = if cannot_refract || self.reflectance(cos_theta, ri) > rng().random() {
+        let direction = if cannot_refract || self.reflectance(cos_theta, ri) > sampler.get_1d() {
             unit_dir.reflect(hit.normal)
         } else {
             unit_dir.refract(hit.normal, ri)
@@ -105,6 +172,195 @@ impl Material for DielectricMaterial {
         Some(Scatter {
             attenuation,
             scattered,
+            pdf: None,
+        })
+    }
+
+    fn albedo(&self, _hit: &HitRecord) -> Vec3 {
+        Vec3::splat(1.0)
+    }
+}
+
+/// Emits light from its front face and reflects nothing.
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vec3) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    pub fn from_texture(emit: Arc<dyn Texture>) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord, _sampler: &mut dyn Sampler) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, hit: &HitRecord) -> Vec3 {
+        if hit.front_face {
+            self.emit.value(hit)
+        } else {
+            Vec3::splat(0.0)
+        }
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
+
+/// Phase function of a medium that scatters equally in every direction.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Vec3) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _ray: &Ray, hit: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        Some(Scatter {
+            attenuation: self.albedo.value(hit),
+            scattered: Ray::new(hit.point, Vec3::random_unit(sampler)),
+            pdf: Some(1.0 / (4.0 * PI)),
         })
     }
+
+    fn eval(&self, _ray: &Ray, hit: &HitRecord, _direction: Vec3) -> Vec3 {
+        self.albedo.value(hit) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _ray: &Ray, _hit: &HitRecord, _direction: Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Vec3 {
+        self.albedo.value(hit)
+    }
+}
+
+/// Henyey-Greenstein phase function, which favors scattering forwards for
+/// positive `g` and backwards for negative `g`.
+pub struct HenyeyGreenstein {
+    albedo: Arc<dyn Texture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    /// # Panics
+    ///
+    /// If `g` isn't strictly between -1 and 1.
+    pub fn new(albedo: Vec3, g: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), g)
+    }
+
+    /// # Panics
+    ///
+    /// If `g` isn't strictly between -1 and 1.
+    pub fn from_texture(albedo: Arc<dyn Texture>, g: f64) -> Self {
+        assert!(g.abs() < 1.0, "asymmetry must be between -1 and 1, got {g}");
+        Self { albedo, g }
+    }
+
+    /// Density of turning by an angle with cosine `cosine`.
+    fn phase(&self, cosine: f64) -> f64 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cosine;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let (xi, v) = sampler.get_2d();
+        let phi = 2.0 * PI * v;
+        let g = self.g;
+        let cosine = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+        let local = Vec3::new(sine * phi.cos(), sine * phi.sin(), cosine);
+        let direction = Onb::new(ray.direction).local(local);
+        Some(Scatter {
+            attenuation: self.albedo.value(hit),
+            scattered: Ray::new(hit.point, direction),
+            pdf: Some(self.phase(cosine)),
+        })
+    }
+
+    fn eval(&self, ray: &Ray, hit: &HitRecord, direction: Vec3) -> Vec3 {
+        self.albedo.value(hit) * self.pdf(ray, hit, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, _hit: &HitRecord, direction: Vec3) -> f64 {
+        self.phase(ray.direction.unit().dot(direction.unit()))
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Vec3 {
+        self.albedo.value(hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    #[test]
+    fn henyey_greenstein_integrates_to_one() {
+        for g in [-0.7, 0.0, 0.3, 0.9] {
+            let hg = HenyeyGreenstein::new(Vec3::splat(1.0), g);
+            let n = 100_000;
+            let integral: f64 = (0..n)
+                .map(|i| {
+                    let cosine = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                    2.0 * PI * hg.phase(cosine) * 2.0 / n as f64
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "g {g}: {integral}");
+        }
+    }
+
+    #[test]
+    fn henyey_greenstein_samples_match_mean_cosine() {
+        let g = 0.6;
+        let hg = HenyeyGreenstein::new(Vec3::splat(1.0), g);
+        let ray = Ray::new(Vec3::splat(0.0), Vec3::new(0.0, 0.0, 2.0));
+        let hit = HitRecord::new(
+            &ray,
+            Vec3::splat(0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            0.0,
+            (0.0, 0.0),
+            Arc::new(Isotropic::new(Vec3::splat(1.0))),
+        );
+        let n = 20_000;
+        let mut mean = 0.0;
+        let mut sampler = IndependentSampler::new(0);
+        for _ in 0..n {
+            let scatter = hg.scatter(&ray, &hit, &mut sampler).unwrap();
+            let direction = scatter.scattered.direction;
+            assert!((direction.len() - 1.0).abs() < 1e-9);
+            let pdf = hg.pdf(&ray, &hit, direction);
+            assert!((scatter.pdf.unwrap() - pdf).abs() < 1e-9 * pdf);
+            mean += direction.z / n as f64;
+        }
+        // The mean cosine of Henyey-Greenstein is `g`.
+        assert!((mean - g).abs() < 0.02, "{mean}");
+    }
 }
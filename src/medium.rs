@@ -0,0 +1,374 @@
+//! Participating media such as smoke and fog.
+
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{HitRecord, HitTarget},
+    interval::Interval,
+    material::{Material, Scatter},
+    perlin::Perlin,
+    ray::Ray,
+    sampler::{IndependentSampler, Sampler},
+    vec3::Vec3,
+};
+
+/// Volume of uniform density filling a convex boundary shape.
+pub struct ConstantMedium {
+    boundary: Box<dyn HitTarget>,
+    neg_inv_density: f64,
+    phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// Medium inside `boundary` that scatters on average once every
+    /// `1 / density` units with the phase function `phase`, usually an
+    /// [`Isotropic`](crate::material::Isotropic) material.
+    pub fn new(boundary: impl HitTarget + 'static, density: f64, phase: Arc<dyn Material>) -> Self {
+        Self {
+            boundary: Box::new(boundary),
+            neg_inv_density: -1.0 / density,
+            phase,
+        }
+    }
+}
+
+impl ConstantMedium {
+    /// Part of `ray_t` that lies inside the boundary.
+    fn inside(&self, ray: &Ray, ray_t: Interval) -> Option<Interval> {
+        // Look for the entry behind the ray as well, so that rays starting
+        // inside the medium still find where it ends.
+        let enter = self.boundary.hit(ray, Interval::UNIVERSE)?;
+        let exit = self
+            .boundary
+            .hit(ray, Interval::new(enter.t + 0.0001, f64::INFINITY))?;
+        let t0 = enter.t.max(ray_t.min);
+        let t1 = exit.t.min(ray_t.max);
+        (t0 < t1).then_some(Interval::new(t0, t1))
+    }
+}
+
+impl HitTarget for ConstantMedium {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let Interval { min: t0, max: t1 } = self.inside(ray, ray_t)?;
+        let speed = ray.direction.len();
+        let xi = crossing_sampler(ray, t0, &self.bounding_box()).get_1d();
+        let distance = self.neg_inv_density * (1.0 - xi).ln();
+        if distance > (t1 - t0) * speed {
+            return None;
+        }
+        let t = t0 + distance / speed;
+        // Scattering inside a medium has no surface, so the normal just
+        // faces the ray.
+        Some(HitRecord::new(
+            ray,
+            ray.at(t),
+            -ray.direction.unit(),
+            t,
+            (0.0, 0.0),
+            self.phase.clone(),
+        ))
+    }
+
+    fn hit_surface(&self, _ray: &Ray, _ray_t: Interval) -> Option<HitRecord> {
+        None
+    }
+
+    fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f64 {
+        self.inside(ray, ray_t).map_or(1.0, |inside| {
+            (self.neg_inv_density.recip() * inside.size() * ray.direction.len()).exp()
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Spatially varying density of a [`HeterogeneousMedium`].
+pub trait Density: Send + Sync {
+    fn density(&self, p: Vec3) -> f64;
+
+    /// Upper bound on the density anywhere.
+    fn max_density(&self) -> f64;
+
+    /// Box outside which the density is zero.
+    fn bounding_box(&self) -> Aabb;
+}
+
+/// Billowing density from Perlin turbulence, between 0 and 1 inside a box.
+pub struct NoiseDensity {
+    noise: Perlin,
+    scale: f64,
+    octaves: u32,
+    bbox: Aabb,
+}
+
+impl NoiseDensity {
+    pub fn new(noise: Perlin, scale: f64, octaves: u32, bbox: Aabb) -> Self {
+        Self {
+            noise,
+            scale,
+            octaves,
+            bbox,
+        }
+    }
+}
+
+impl Density for NoiseDensity {
+    fn density(&self, p: Vec3) -> f64 {
+        self.noise.turbulence(self.scale * p, self.octaves).min(1.0)
+    }
+
+    fn max_density(&self) -> f64 {
+        1.0
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+/// Volume whose density varies through space, sampled with delta tracking
+/// and ratio tracking against its largest extinction.
+pub struct HeterogeneousMedium {
+    density: Box<dyn Density>,
+    absorption: f64,
+    scattering: f64,
+    /// Extinction at the largest density, bounding it everywhere.
+    majorant: f64,
+    phase: Arc<dyn Material>,
+    /// Ends paths absorbed in the medium.
+    absorber: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    /// Medium absorbing and scattering `absorption` and `scattering` times the
+    /// density per unit of distance. Scattering follows `phase`, and absorbed
+    /// paths pick up the radiance `emission`.
+    pub fn new(
+        density: impl Density + 'static,
+        absorption: f64,
+        scattering: f64,
+        emission: Vec3,
+        phase: Arc<dyn Material>,
+    ) -> Self {
+        let majorant = (absorption + scattering) * density.max_density();
+        Self {
+            density: Box::new(density),
+            absorption,
+            scattering,
+            majorant,
+            phase,
+            absorber: Arc::new(VolumeEmission { emission }),
+        }
+    }
+
+    /// Part of `ray_t` inside the density's box, or `None` if the ray misses
+    /// it or the medium is empty.
+    fn inside(&self, ray: &Ray, ray_t: Interval) -> Option<Interval> {
+        if self.majorant <= 0.0 {
+            return None;
+        }
+        let d = ray.direction;
+        let inv_dir = Vec3::new(1.0 / d.x, 1.0 / d.y, 1.0 / d.z);
+        self.density.bounding_box().clip(ray, inv_dir, ray_t)
+    }
+
+    /// Distance along the ray, in units of `t`, to the next tentative
+    /// collision against the majorant.
+    fn step(&self, ray: &Ray, sampler: &mut dyn Sampler) -> f64 {
+        -(1.0 - sampler.get_1d()).ln() / (self.majorant * ray.direction.len())
+    }
+}
+
+impl HitTarget for HeterogeneousMedium {
+    /// Delta tracking: tentative collisions are real with probability
+    /// extinction over majorant, and real ones absorb or scatter in
+    /// proportion to their coefficients.
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let inside = self.inside(ray, ray_t)?;
+        let mut sampler = crossing_sampler(ray, inside.min, &self.bounding_box());
+        let mut t = inside.min;
+        loop {
+            t += self.step(ray, &mut sampler);
+            if t >= inside.max {
+                return None;
+            }
+            let point = ray.at(t);
+            let density = self.density.density(point);
+            let xi = sampler.get_1d() * self.majorant;
+            let material = if xi < self.absorption * density {
+                &self.absorber
+            } else if xi < (self.absorption + self.scattering) * density {
+                &self.phase
+            } else {
+                continue;
+            };
+            return Some(HitRecord::new(
+                ray,
+                point,
+                -ray.direction.unit(),
+                t,
+                (0.0, 0.0),
+                material.clone(),
+            ));
+        }
+    }
+
+    fn hit_surface(&self, _ray: &Ray, _ray_t: Interval) -> Option<HitRecord> {
+        None
+    }
+
+    /// Ratio tracking: every tentative collision scales the estimate by the
+    /// chance that it was a null one.
+    fn transmittance(&self, ray: &Ray, ray_t: Interval) -> f64 {
+        let Some(inside) = self.inside(ray, ray_t) else {
+            return 1.0;
+        };
+        let extinction = self.absorption + self.scattering;
+        let mut sampler = crossing_sampler(ray, inside.min, &self.bounding_box());
+        let mut transmittance = 1.0;
+        let mut t = inside.min;
+        loop {
+            t += self.step(ray, &mut sampler);
+            if t >= inside.max {
+                return transmittance;
+            }
+            let density = self.density.density(ray.at(t));
+            transmittance *= 1.0 - extinction * density / self.majorant;
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.density.bounding_box()
+    }
+}
+
+/// Random numbers for the crossing of the medium in `bbox` that starts at
+/// `t` along `ray`. A ray's seed alone would have every medium it passes
+/// through draw the same free flights, so the crossing is keyed in too.
+fn crossing_sampler(ray: &Ray, t: f64, bbox: &Aabb) -> IndependentSampler {
+    let (min, max) = (bbox.min(), bbox.max());
+    let key = [t, min.x, min.y, min.z, max.x, max.y, max.z]
+        .iter()
+        .fold(0u64, |acc, x| acc.rotate_left(17) ^ x.to_bits());
+    IndependentSampler::keyed(ray.seed, key)
+}
+
+/// Material of absorption events, which end the path with the medium's
+/// emission.
+struct VolumeEmission {
+    emission: Vec3,
+}
+
+impl Material for VolumeEmission {
+    fn scatter(&self, _ray: &Ray, _hit: &HitRecord, _sampler: &mut dyn Sampler) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, _hit: &HitRecord) -> Vec3 {
+        self.emission
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::VoxelGrid, hit::HitWorld, material::Isotropic, sphere::Sphere};
+
+    #[test]
+    fn transmittance_follows_density() {
+        let phase = Arc::new(Isotropic::new(Vec3::splat(1.0)));
+        let boundary = Sphere::new(Vec3::splat(0.0), 1.0, phase.clone());
+        let medium = ConstantMedium::new(boundary, 0.5, phase);
+
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let n = 20_000;
+        // From outside the ray crosses 2 units, from the center only 1.
+        for (origin, length) in [
+            (Vec3::new(0.0, 0.0, -3.0), 2.0_f64),
+            (Vec3::splat(0.0), 1.0),
+        ] {
+            let ray = Ray::new(origin, Vec3::new(0.0, 0.0, 2.0));
+            let mut passed = 0;
+            for seed in 0..n {
+                match medium.hit(&ray.with_seed(seed), ray_t) {
+                    Some(hit) => assert!(hit.point.len() <= 1.0 + 1e-9),
+                    None => passed += 1,
+                }
+            }
+            let expected = (-0.5 * length).exp();
+            let transmittance = passed as f64 / n as f64;
+            assert!((transmittance - expected).abs() < 0.02, "{transmittance}");
+            let exact = medium.transmittance(&ray, ray_t);
+            assert!((exact - expected).abs() < 1e-3, "{exact}");
+        }
+    }
+
+    #[test]
+    fn tracking_matches_optical_depth() {
+        // Density rising linearly from 0 to 1 across the unit cube, so the
+        // optical depth through it along x is half the extinction.
+        let n = 16;
+        let values = (0..n * n * n)
+            .map(|i| ((i % n) as f32 + 0.5) / n as f32)
+            .collect::<Vec<_>>();
+        let bbox = Aabb::from_points(Vec3::splat(0.0), Vec3::splat(1.0));
+        let grid = VoxelGrid::new([n; 3], &values, bbox);
+        let phase = Arc::new(Isotropic::new(Vec3::splat(1.0)));
+        let medium = HeterogeneousMedium::new(grid, 0.5, 1.5, Vec3::splat(0.0), phase);
+
+        let ray = Ray::new(Vec3::new(-1.0, 0.5, 0.5), Vec3::new(2.0, 0.0, 0.0));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let expected = (-1.0f64).exp();
+        let n = 20_000;
+        let passed = (0..n)
+            .filter(|&seed| medium.hit(&ray.with_seed(seed), ray_t).is_none())
+            .count();
+        let delta = passed as f64 / n as f64;
+        let ratio = (0..n)
+            .map(|seed| medium.transmittance(&ray.with_seed(seed), ray_t))
+            .sum::<f64>()
+            / n as f64;
+        assert!((delta - expected).abs() < 0.02, "{delta}");
+        assert!((ratio - expected).abs() < 0.01, "{ratio}");
+    }
+
+    #[test]
+    fn separate_media_draw_separately() {
+        // Two media one unit of optical depth deep each, one behind the other.
+        let phase = Arc::new(Isotropic::new(Vec3::splat(1.0)));
+        let mut spheres = HitWorld::new();
+        let mut grids = HitWorld::new();
+        for z in [0.0, 4.0] {
+            let center = Vec3::new(0.0, 0.0, z);
+            let boundary = Sphere::new(center, 1.0, phase.clone());
+            spheres.push(ConstantMedium::new(boundary, 0.5, phase.clone()));
+            let bbox = Aabb::from_points(center - Vec3::splat(1.0), center + Vec3::splat(1.0));
+            let grid = VoxelGrid::new([1; 3], &[1.0], bbox);
+            grids.push(HeterogeneousMedium::new(
+                grid,
+                0.0,
+                0.5,
+                Vec3::splat(0.0),
+                phase.clone(),
+            ));
+        }
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let expected = (-2.0f64).exp();
+        let n = 20_000;
+        let passed = (0..n)
+            .filter(|&seed| spheres.hit(&ray.with_seed(seed), ray_t).is_none())
+            .count();
+        let delta = passed as f64 / n as f64;
+        let ratio = (0..n)
+            .map(|seed| grids.transmittance(&ray.with_seed(seed), ray_t))
+            .sum::<f64>()
+            / n as f64;
+        assert!((delta - expected).abs() < 0.01, "{delta}");
+        assert!((ratio - expected).abs() < 0.01, "{ratio}");
+    }
+}
@@ -0,0 +1,283 @@
+use std::{ops::Range, sync::Arc};
+
+use crate::{
+    aabb::Aabb,
+    bvh::Bvh,
+    hit::{HitRecord, HitTarget},
+    interval::Interval,
+    light::{Light, TriangleLight},
+    material::Material,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// Vertex and index buffers of an indexed triangle mesh.
+///
+/// `normals` and `uvs` are either empty or hold one entry per position.
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub indices: Vec<[u32; 3]>,
+    /// Materials for consecutive ranges of faces. Every face must be covered
+    /// by exactly one range.
+    pub materials: Vec<(Range<usize>, Arc<dyn Material>)>,
+}
+
+impl MeshData {
+    /// Mesh with a single material for every face and no normals or UVs.
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[u32; 3]>, material: Arc<dyn Material>) -> Self {
+        let faces = 0..indices.len();
+        Self {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            indices,
+            materials: vec![(faces, material)],
+        }
+    }
+
+    fn vertices(&self, face: usize) -> [Vec3; 3] {
+        self.indices[face].map(|i| self.positions[i as usize])
+    }
+}
+
+pub struct TriangleMesh {
+    triangles: Bvh<Triangle>,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData) -> Self {
+        Self::from_shared(Arc::new(data))
+    }
+
+    /// Builds a mesh over buffers that may be shared with other meshes.
+    pub fn from_shared(data: Arc<MeshData>) -> Self {
+        let vertex_count = data.positions.len();
+        assert!(
+            data.normals.is_empty() || data.normals.len() == vertex_count,
+            "mesh has {} normals for {vertex_count} vertices",
+            data.normals.len()
+        );
+        assert!(
+            data.uvs.is_empty() || data.uvs.len() == vertex_count,
+            "mesh has {} UVs for {vertex_count} vertices",
+            data.uvs.len()
+        );
+        assert!(
+            data.indices
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < vertex_count),
+            "mesh index out of range"
+        );
+
+        let triangles = (0..data.indices.len())
+            .map(|face| {
+                let material = data
+                    .materials
+                    .iter()
+                    .position(|(faces, _)| faces.contains(&face))
+                    .unwrap_or_else(|| panic!("mesh face {face} has no material"));
+                Triangle {
+                    mesh: data.clone(),
+                    face: face as u32,
+                    material: material as u32,
+                }
+            })
+            .collect();
+
+        Self {
+            triangles: Bvh::new(triangles),
+        }
+    }
+}
+
+impl HitTarget for TriangleMesh {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        self.triangles.hit(ray, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.triangles.bounding_box()
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        self.triangles.lights(lights);
+    }
+}
+
+/// A single face of a [`TriangleMesh`].
+struct Triangle {
+    mesh: Arc<MeshData>,
+    face: u32,
+    material: u32,
+}
+
+impl HitTarget for Triangle {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let mesh = &*self.mesh;
+        let face = self.face as usize;
+        let [p0, p1, p2] = mesh.vertices(face);
+        let (t, [b0, b1, b2]) = intersect_triangle(ray, ray_t, p0, p1, p2)?;
+
+        let point = b0 * p0 + b1 * p1 + b2 * p2;
+        let cross = (p1 - p0).cross(p2 - p0);
+        let geometric_normal = cross.unit();
+        let outward_normal = if mesh.normals.is_empty() {
+            geometric_normal
+        } else {
+            let [n0, n1, n2] = mesh.indices[face].map(|i| mesh.normals[i as usize]);
+            let shading_normal = (b0 * n0 + b1 * n1 + b2 * n2).unit();
+            // Keep the shading normal on the same side as the geometry so
+            // that front faces are still classified by winding order.
+            if shading_normal.dot(geometric_normal) < 0.0 {
+                -shading_normal
+            } else {
+                shading_normal
+            }
+        };
+
+        let [uv0, uv1, uv2] = if mesh.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        } else {
+            mesh.indices[face].map(|i| mesh.uvs[i as usize])
+        };
+        let uv = (
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+        );
+        // Ratio of the triangle's area in u, v space to its area in space.
+        let uv_area = ((uv1.0 - uv0.0) * (uv2.1 - uv0.1) - (uv2.0 - uv0.0) * (uv1.1 - uv0.1)).abs();
+        let area = cross.len();
+
+        let material = mesh.materials[self.material as usize].1.clone();
+        let mut hit = HitRecord::new(ray, point, outward_normal, t, uv, material);
+        if area > 0.0 {
+            hit.uv_density = (uv_area / area).sqrt();
+        }
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [p0, p1, p2] = self.mesh.vertices(self.face as usize);
+        Aabb::from_points(p0, p1).union(Aabb::from_points(p1, p2))
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        let (_, material) = &self.mesh.materials[self.material as usize];
+        if material.is_emissive() {
+            let vertices = self.mesh.vertices(self.face as usize);
+            lights.push(Arc::new(TriangleLight::new(vertices)));
+        }
+    }
+}
+
+/// Watertight ray/triangle intersection (Woop, Benthin and Wald 2013).
+///
+/// Returns the ray parameter and the barycentric weights of `p0`, `p1` and
+/// `p2`. Rays through a shared edge or vertex never slip between adjacent
+/// triangles.
+pub fn intersect_triangle(
+    ray: &Ray,
+    ray_t: Interval,
+    p0: Vec3,
+    p1: Vec3,
+    p2: Vec3,
+) -> Option<(f64, [f64; 3])> {
+    let d = ray.direction;
+
+    // Permute axes so that the ray direction's largest component is z.
+    let kz = if d.x.abs() > d.y.abs() && d.x.abs() > d.z.abs() {
+        0
+    } else if d.y.abs() > d.z.abs() {
+        1
+    } else {
+        2
+    };
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    let permute = |v: Vec3| Vec3::new(v[kx], v[ky], v[kz]);
+
+    let d = permute(d);
+    let shear_x = -d.x / d.z;
+    let shear_y = -d.y / d.z;
+    let shear_z = 1.0 / d.z;
+
+    // Transform the vertices into a space where the ray starts at the origin
+    // and points down +z.
+    let [p0, p1, p2] = [p0, p1, p2].map(|p| {
+        let p = permute(p - ray.origin);
+        Vec3::new(p.x + shear_x * p.z, p.y + shear_y * p.z, p.z * shear_z)
+    });
+
+    let e0 = p1.x * p2.y - p1.y * p2.x;
+    let e1 = p2.x * p0.y - p2.y * p0.x;
+    let e2 = p0.x * p1.y - p0.y * p1.x;
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (e0 * p0.z + e1 * p1.z + e2 * p2.z) / det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    Some((t, [e0 / det, e1 / det, e2 / det]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAY_T: Interval = Interval::new(0.001, f64::INFINITY);
+
+    fn triangle() -> [Vec3; 3] {
+        [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn hits_interior() {
+        let [p0, p1, p2] = triangle();
+        let ray = Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let (t, [b0, b1, b2]) = intersect_triangle(&ray, RAY_T, p0, p1, p2).unwrap();
+        assert!((t - 1.0).abs() < 1e-12);
+        assert!((b0 - 0.5).abs() < 1e-12);
+        assert!((b1 - 0.25).abs() < 1e-12);
+        assert!((b2 - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn misses_exterior() {
+        let [p0, p1, p2] = triangle();
+        let ray = Ray::new(Vec3::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(intersect_triangle(&ray, RAY_T, p0, p1, p2).is_none());
+    }
+
+    #[test]
+    fn shared_edge_is_watertight() {
+        // Two triangles forming a unit square, split along its diagonal.
+        let a = Vec3::new(0.0, 0.0, 0.0);
+        let b = Vec3::new(1.0, 0.0, 0.0);
+        let c = Vec3::new(1.0, 1.0, 0.0);
+        let d = Vec3::new(0.0, 1.0, 0.0);
+        for i in 1..100 {
+            let s = i as f64 / 100.0;
+            let ray = Ray::new(Vec3::new(s, s, 1.0), Vec3::new(0.0, 0.0, -1.0));
+            let hits = [
+                intersect_triangle(&ray, RAY_T, a, b, c),
+                intersect_triangle(&ray, RAY_T, a, c, d),
+            ];
+            assert!(hits.iter().any(Option::is_some), "ray {i} slipped through");
+        }
+    }
+}
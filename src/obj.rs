@@ -0,0 +1,562 @@
+//! Wavefront OBJ and MTL loading.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::{FromStr, SplitWhitespace},
+    sync::Arc,
+};
+
+use crate::{
+    material::{DielectricMaterial, DiffuseLight, LambertianMaterial, Material, MetalMaterial},
+    mesh::MeshData,
+    texture::{AddressMode, ImageTexture, TextureFilter},
+    vec3::Vec3,
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+/// One group (`g` or `o`) of an OBJ file.
+pub struct ObjMesh {
+    pub name: String,
+    pub data: MeshData,
+}
+
+/// Surface description from an MTL file.
+#[derive(Debug, Clone)]
+pub struct Mtl {
+    pub name: String,
+    /// `Kd`
+    pub diffuse: Vec3,
+    /// `map_Kd`, used in place of `diffuse` when present. Relative paths are
+    /// resolved against the MTL file by [`load_obj`].
+    pub diffuse_map: Option<PathBuf>,
+    /// `Ks`
+    pub specular: Vec3,
+    /// `Ke`
+    pub emission: Vec3,
+    /// `Ns`
+    pub shininess: f64,
+    /// `Ni`
+    pub refraction_index: f64,
+    /// `d`, or one minus `Tr`
+    pub dissolve: f64,
+}
+
+impl Mtl {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            diffuse: Vec3::splat(0.8),
+            diffuse_map: None,
+            specular: Vec3::splat(0.0),
+            emission: Vec3::splat(0.0),
+            shininess: 0.0,
+            refraction_index: 1.5,
+            dissolve: 1.0,
+        }
+    }
+
+    /// Picks the closest of the renderer's materials: emissive surfaces
+    /// become lights, transparent ones glass, surfaces with a stronger
+    /// specular than diffuse color become metal and everything else is
+    /// Lambertian. Loads the diffuse map, if any.
+    pub fn to_material(&self) -> Result<Arc<dyn Material>, ObjError> {
+        let max = |c: Vec3| c.x.max(c.y).max(c.z);
+        let material: Arc<dyn Material> = if max(self.emission) > 0.0 {
+            Arc::new(DiffuseLight::new(self.emission))
+        } else if self.dissolve < 1.0 {
+            Arc::new(DielectricMaterial::new(self.refraction_index))
+        } else if self.diffuse_map.is_none() && max(self.specular) > max(self.diffuse) {
+            // Map the Phong exponent onto a roughness-like fuzz factor.
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt().min(1.0);
+            Arc::new(MetalMaterial::new(self.specular, fuzz))
+        } else if let Some(path) = &self.diffuse_map {
+            let texture = ImageTexture::load(path, AddressMode::Wrap, TextureFilter::Trilinear)
+                .map_err(|source| ObjError::Io {
+                    path: path.clone(),
+                    source,
+                })?;
+            Arc::new(LambertianMaterial::from_texture(Arc::new(texture)))
+        } else {
+            Arc::new(LambertianMaterial::new(self.diffuse))
+        };
+        Ok(material)
+    }
+}
+
+/// Loads every group of an OBJ file, along with the materials of any MTL
+/// libraries it references. Polygons are triangulated as fans and faces
+/// without a `usemtl` get a grey Lambertian material.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Vec<ObjMesh>, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    parse_obj(&source, path, |name| {
+        let mtl_path = dir.join(name);
+        let source = read(&mtl_path)?;
+        let mut mtls = parse_mtl(&source, &mtl_path)?;
+        let mtl_dir = mtl_path.parent().unwrap_or(Path::new(""));
+        for mtl in &mut mtls {
+            mtl.diffuse_map = mtl.diffuse_map.take().map(|map| mtl_dir.join(map));
+        }
+        Ok(mtls)
+    })
+}
+
+/// Parses the contents of an MTL file. `path` is only used for errors.
+pub fn parse_mtl(source: &str, path: &Path) -> Result<Vec<Mtl>, ObjError> {
+    let mut materials: Vec<Mtl> = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let mut p = LineParser::new(line, path, index + 1);
+        let Some(keyword) = p.keyword() else {
+            continue;
+        };
+        if keyword == "newmtl" {
+            materials.push(Mtl::new(p.rest()?));
+            continue;
+        }
+
+        let Some(mtl) = materials.last_mut() else {
+            return Err(p.error(format!("`{keyword}` before any `newmtl`")));
+        };
+        match keyword {
+            "Kd" => mtl.diffuse = p.vec3()?,
+            "Ks" => mtl.specular = p.vec3()?,
+            "Ke" => mtl.emission = p.vec3()?,
+            "Ns" => mtl.shininess = p.number()?,
+            "Ni" => mtl.refraction_index = p.number()?,
+            "d" => mtl.dissolve = p.number()?,
+            "Tr" => mtl.dissolve = 1.0 - p.number::<f64>()?,
+            "map_Kd" => {
+                // Options like scaling and clamping aren't supported, so the
+                // map is used as if they weren't there.
+                p.skip_map_options();
+                mtl.diffuse_map = Some(PathBuf::from(p.rest()?));
+                continue;
+            }
+            // Ambient color, illumination model, other texture maps and the
+            // like have no equivalent here.
+            _ => continue,
+        }
+        p.finish()?;
+    }
+    Ok(materials)
+}
+
+/// Parses the contents of an OBJ file, calling `load_mtl` for each `mtllib`.
+/// `path` is only used for errors.
+pub fn parse_obj(
+    source: &str,
+    path: &Path,
+    mut load_mtl: impl FnMut(&str) -> Result<Vec<Mtl>, ObjError>,
+) -> Result<Vec<ObjMesh>, ObjError> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut materials = HashMap::new();
+    let default_material: Arc<dyn Material> = Arc::new(LambertianMaterial::new(Vec3::splat(0.8)));
+
+    let mut meshes = Vec::new();
+    let mut group = GroupBuilder::new("default".to_string(), default_material);
+    for (index, line) in source.lines().enumerate() {
+        let mut p = LineParser::new(line, path, index + 1);
+        let Some(keyword) = p.keyword() else {
+            continue;
+        };
+        match keyword {
+            "v" => {
+                positions.push(p.vec3()?);
+                // An optional w coordinate or vertex color is allowed but
+                // unused.
+                for _ in 0..3 {
+                    p.number_or(0.0)?;
+                }
+            }
+            "vn" => normals.push(p.vec3()?),
+            "vt" => {
+                let u = p.number()?;
+                let v = p.number_or(0.0)?;
+                // An optional w coordinate is allowed but unused.
+                p.number_or(0.0)?;
+                uvs.push((u, v));
+            }
+            "f" => {
+                let counts = [positions.len(), uvs.len(), normals.len()];
+                let face = p
+                    .by_ref()
+                    .map(|vertex| parse_face_vertex(vertex, counts))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|message| p.error(message))?;
+                if face.len() < 3 {
+                    return Err(p.error(format!("face has {} vertices", face.len())));
+                }
+                group.push_face(&face, &positions, &uvs, &normals);
+                continue;
+            }
+            "g" | "o" => {
+                let name = p.rest().unwrap_or_default();
+                let material = group.material.clone();
+                let next = GroupBuilder::new(name, material);
+                meshes.extend(std::mem::replace(&mut group, next).finish());
+                continue;
+            }
+            "usemtl" => {
+                let name = p.rest()?;
+                let Some(material) = materials.get(&name) else {
+                    return Err(p.error(format!("unknown material `{name}`")));
+                };
+                group.set_material(Arc::clone(material));
+                continue;
+            }
+            "mtllib" => {
+                for name in p.by_ref().collect::<Vec<_>>() {
+                    for mtl in load_mtl(name)? {
+                        materials.insert(mtl.name.clone(), mtl.to_material()?);
+                    }
+                }
+                continue;
+            }
+            // Smoothing groups, lines, points and free-form geometry.
+            _ => continue,
+        }
+        p.finish()?;
+    }
+    meshes.extend(group.finish());
+    Ok(meshes)
+}
+
+/// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` face vertex into zero-based
+/// indices. `counts` holds the number of positions, UVs and normals defined
+/// so far, which negative indices are relative to.
+fn parse_face_vertex(vertex: &str, counts: [usize; 3]) -> Result<[Option<usize>; 3], String> {
+    let mut indices = [None; 3];
+    let mut parts = vertex.split('/');
+    for (slot, count) in indices.iter_mut().zip(counts) {
+        let Some(part) = parts.next() else {
+            break;
+        };
+        if part.is_empty() {
+            continue;
+        }
+        let index = part
+            .parse::<i64>()
+            .map_err(|_| format!("invalid face vertex `{vertex}`"))?;
+        let resolved = if index < 0 {
+            count as i64 + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(format!("index {index} out of range in `{vertex}`"));
+        }
+        *slot = Some(resolved as usize);
+    }
+    if parts.next().is_some() || indices[0].is_none() {
+        return Err(format!("invalid face vertex `{vertex}`"));
+    }
+    Ok(indices)
+}
+
+struct GroupBuilder {
+    name: String,
+    material: Arc<dyn Material>,
+    /// Face at which `material` started being used.
+    material_start: usize,
+    data: MeshData,
+    vertices: HashMap<[Option<usize>; 3], u32>,
+    has_uvs: bool,
+    has_normals: bool,
+}
+
+impl GroupBuilder {
+    fn new(name: String, material: Arc<dyn Material>) -> Self {
+        Self {
+            name,
+            material,
+            material_start: 0,
+            data: MeshData {
+                positions: Vec::new(),
+                normals: Vec::new(),
+                uvs: Vec::new(),
+                indices: Vec::new(),
+                materials: Vec::new(),
+            },
+            vertices: HashMap::new(),
+            has_uvs: true,
+            has_normals: true,
+        }
+    }
+
+    fn set_material(&mut self, material: Arc<dyn Material>) {
+        self.close_material_range();
+        self.material = material;
+    }
+
+    fn close_material_range(&mut self) {
+        let end = self.data.indices.len();
+        if end > self.material_start {
+            let range = self.material_start..end;
+            self.data.materials.push((range, self.material.clone()));
+        }
+        self.material_start = end;
+    }
+
+    fn push_face(
+        &mut self,
+        face: &[[Option<usize>; 3]],
+        positions: &[Vec3],
+        uvs: &[(f64, f64)],
+        normals: &[Vec3],
+    ) {
+        let indices = face
+            .iter()
+            .map(|&key @ [v, vt, vn]| {
+                self.has_uvs &= vt.is_some();
+                self.has_normals &= vn.is_some();
+                *self.vertices.entry(key).or_insert_with(|| {
+                    let data = &mut self.data;
+                    data.positions.push(positions[v.unwrap()]);
+                    data.uvs.push(vt.map_or((0.0, 0.0), |i| uvs[i]));
+                    data.normals
+                        .push(vn.map_or(Vec3::splat(0.0), |i| normals[i]));
+                    (data.positions.len() - 1) as u32
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for i in 1..indices.len() - 1 {
+            self.data
+                .indices
+                .push([indices[0], indices[i], indices[i + 1]]);
+        }
+    }
+
+    fn finish(mut self) -> Option<ObjMesh> {
+        if self.data.indices.is_empty() {
+            return None;
+        }
+        self.close_material_range();
+        // Vertex attributes are all or nothing per mesh.
+        if !self.has_uvs {
+            self.data.uvs.clear();
+        }
+        if !self.has_normals {
+            self.data.normals.clear();
+        }
+        Some(ObjMesh {
+            name: self.name,
+            data: self.data,
+        })
+    }
+}
+
+/// Whitespace separated fields of a single line, with errors pointing at it.
+struct LineParser<'a> {
+    fields: SplitWhitespace<'a>,
+    path: &'a Path,
+    line: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(line: &'a str, path: &'a Path, number: usize) -> Self {
+        let line = line.split_once('#').map_or(line, |(line, _)| line);
+        Self {
+            fields: line.split_whitespace(),
+            path,
+            line: number,
+        }
+    }
+
+    fn keyword(&mut self) -> Option<&'a str> {
+        self.fields.next()
+    }
+
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, ObjError> {
+        match self.fields.next() {
+            Some(field) => field
+                .parse()
+                .map_err(|_| self.error(format!("expected a number, found `{field}`"))),
+            None => Err(self.error("expected a number".to_string())),
+        }
+    }
+
+    fn number_or(&mut self, default: f64) -> Result<f64, ObjError> {
+        match self.fields.clone().next() {
+            Some(_) => self.number(),
+            None => Ok(default),
+        }
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, ObjError> {
+        Ok(Vec3::new(self.number()?, self.number()?, self.number()?))
+    }
+
+    /// Skips the options before the file of a texture map statement, such as
+    /// `-s 2 2 1` or `-clamp on`.
+    fn skip_map_options(&mut self) {
+        while let Some(option) = self.fields.clone().next().filter(|f| f.starts_with('-')) {
+            self.fields.next();
+            match option {
+                // Offset, scale and turbulence take one to three numbers.
+                "-o" | "-s" | "-t" => {
+                    for _ in 0..3 {
+                        let field = self.fields.clone().next();
+                        if field.is_none_or(|f| f.parse::<f64>().is_err()) {
+                            break;
+                        }
+                        self.fields.next();
+                    }
+                }
+                "-mm" => {
+                    self.fields.nth(1);
+                }
+                _ => {
+                    self.fields.next();
+                }
+            }
+        }
+    }
+
+    /// Remainder of the line, which may contain spaces.
+    fn rest(&mut self) -> Result<String, ObjError> {
+        let rest = self.fields.by_ref().collect::<Vec<_>>().join(" ");
+        if rest.is_empty() {
+            Err(self.error("expected a name".to_string()))
+        } else {
+            Ok(rest)
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), ObjError> {
+        match self.fields.next() {
+            Some(field) => Err(self.error(format!("unexpected `{field}`"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> Iterator for LineParser<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fields.next()
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Vec<ObjMesh>, ObjError> {
+        parse_obj(source, Path::new("test.obj"), |_| {
+            parse_mtl("newmtl red\nKd 1 0 0\n", Path::new("test.mtl"))
+        })
+    }
+
+    #[test]
+    fn triangulates_polygons() {
+        let meshes = parse(
+            "v 0 0 0\nv 1 0 0 1\nv 1 1 0 0.5 0.2 0.9\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nf 1/1 2/2 3/3 4/4\n",
+        )
+        .unwrap();
+        assert_eq!(meshes.len(), 1);
+        let data = &meshes[0].data;
+        assert_eq!(data.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(data.uvs.len(), 4);
+        assert!(data.normals.is_empty());
+    }
+
+    #[test]
+    fn splits_groups_and_materials() {
+        let meshes = parse(
+            "mtllib test.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\ng a\nf 1 2 3\nusemtl red\nf -3 -2 -1\ng b\nf 1 2 3\n",
+        )
+        .unwrap();
+        let names = meshes.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b"]);
+        let ranges = meshes[0]
+            .data
+            .materials
+            .iter()
+            .map(|(range, _)| range.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, [0..1, 1..2]);
+        assert_eq!(meshes[1].data.materials.len(), 1);
+    }
+
+    #[test]
+    fn skips_texture_map_options() {
+        let source = "newmtl a\nmap_Kd -s 2 2 -clamp on -mm 0 1 -o -0.5 wood grain.png\n";
+        let mtls = parse_mtl(source, Path::new("test.mtl")).unwrap();
+        assert_eq!(mtls[0].diffuse_map, Some(PathBuf::from("wood grain.png")));
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let err = parse("v 0 0 0\nv 1 zero 0\n").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "test.obj:2: expected a number, found `zero`"
+        );
+        let err = parse("v 0 0 0\nusemtl blue\n").err().unwrap();
+        assert_eq!(err.to_string(), "test.obj:2: unknown material `blue`");
+        let err = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1//1 2 3\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "test.obj:4: index 1 out of range in `1//1`"
+        );
+    }
+}
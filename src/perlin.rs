@@ -0,0 +1,131 @@
+//! Gradient noise for procedural textures.
+
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::vec3::Vec3;
+
+const POINT_COUNT: usize = 256;
+
+/// Perlin gradient noise over a repeating lattice of random unit vectors.
+pub struct Perlin {
+    gradients: [Vec3; POINT_COUNT],
+    perm_x: [u8; POINT_COUNT],
+    perm_y: [u8; POINT_COUNT],
+    perm_z: [u8; POINT_COUNT],
+}
+
+impl Perlin {
+    /// Noise generator whose lattice is determined by `seed`, so that the same
+    /// scene always renders the same pattern.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let gradients = std::array::from_fn(|_| {
+            loop {
+                let v = Vec3::new(
+                    rng.random_range(-1.0..1.0),
+                    rng.random_range(-1.0..1.0),
+                    rng.random_range(-1.0..1.0),
+                );
+                let len_squared = v.len_squared();
+                if 1e-160 < len_squared && len_squared <= 1.0 {
+                    break v.unit();
+                }
+            }
+        });
+        let mut permutation = || {
+            let mut perm = std::array::from_fn(|i| i as u8);
+            perm.shuffle(&mut rng);
+            perm
+        };
+        let perm_x = permutation();
+        let perm_y = permutation();
+        let perm_z = permutation();
+        Self {
+            gradients,
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+
+    /// Noise at `p`, in about `[-1, 1]`. Zero at every lattice point.
+    pub fn noise(&self, p: Vec3) -> f64 {
+        let (u, v, w) = (p.x - p.x.floor(), p.y - p.y.floor(), p.z - p.z.floor());
+        let (i, j, k) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+
+        let mut corners = [[[Vec3::splat(0.0); 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let hash = self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.gradients[hash as usize];
+                }
+            }
+        }
+        interpolate(&corners, u, v, w)
+    }
+
+    /// Sum of `octaves` layers of noise, each at twice the frequency and half
+    /// the amplitude of the last, folded to be non-negative.
+    pub fn turbulence(&self, p: Vec3, octaves: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            sum += weight * self.noise(p);
+            weight *= 0.5;
+            p = p * 2.0;
+        }
+        sum.abs()
+    }
+}
+
+/// Trilinear blend of the corners' gradient contributions, eased with a
+/// Hermite cubic so that the noise is smooth across cells.
+fn interpolate(corners: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    let hermite = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (uu, vv, ww) = (hermite(u), hermite(v), hermite(w));
+
+    let mut sum = 0.0;
+    for (i, plane) in corners.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                let offset = Vec3::new(u - fi, v - fj, w - fk);
+                sum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                    * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                    * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                    * gradient.dot(offset);
+            }
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_smooth_and_seeded() {
+        let a = Perlin::new(1);
+        let b = Perlin::new(1);
+        let c = Perlin::new(2);
+        let mut differs = false;
+        for i in 0..1000 {
+            let t = i as f64 * 0.137;
+            let p = Vec3::new(t, 0.5 * t - 3.0, 7.0 - 0.25 * t);
+            let n = a.noise(p);
+            assert!(n.abs() <= 1.0, "noise {n} at {p:?}");
+            assert_eq!(n, b.noise(p));
+            differs |= n != c.noise(p);
+
+            let step = a.noise(p + Vec3::splat(1e-6)) - n;
+            assert!(step.abs() < 1e-5);
+        }
+        assert!(differs);
+        assert_eq!(a.noise(Vec3::new(3.0, -2.0, 5.0)), 0.0);
+    }
+}
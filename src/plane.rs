@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{HitRecord, HitTarget},
+    interval::Interval,
+    material::Material,
+    onb::Onb,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// Infinite plane through a point, facing along its normal.
+pub struct Plane {
+    point: Vec3,
+    basis: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    /// Plane whose `u, v` texture coordinates are distances from `point` along
+    /// two perpendicular directions in the plane.
+    pub fn new(point: Vec3, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        Self {
+            point,
+            basis: Onb::new(normal),
+            material,
+        }
+    }
+}
+
+impl HitTarget for Plane {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let normal = self.basis.w;
+        let denom = normal.dot(ray.direction);
+        if denom.abs() < 1e-12 * ray.direction.len() {
+            return None;
+        }
+        let t = normal.dot(self.point - ray.origin) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let offset = point - self.point;
+        let uv = (offset.dot(self.basis.u), offset.dot(self.basis.v));
+        let material = self.material.clone();
+        let mut hit = HitRecord::new(ray, point, normal, t, uv, material);
+        hit.uv_density = 1.0;
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::UNIVERSE
+    }
+}
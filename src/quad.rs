@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::Aabb,
+    hit::{HitRecord, HitTarget, HitWorld},
+    interval::Interval,
+    light::{Light, QuadLight},
+    material::Material,
+    ray::Ray,
+    vec3::Vec3,
+};
+
+/// Parallelogram spanned by two edges from a corner.
+pub struct Quad {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    uv_density: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    /// Quad with corners `origin`, `origin + u`, `origin + u + v` and
+    /// `origin + v`, facing along `u × v`. Its `u, v` texture coordinates run
+    /// along the edges.
+    pub fn new(origin: Vec3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = u.cross(v);
+        Self {
+            origin,
+            u,
+            v,
+            normal: n.unit(),
+            uv_density: 1.0 / n.len().sqrt(),
+            material,
+        }
+    }
+}
+
+impl HitTarget for Quad {
+    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let (t, alpha, beta) = intersect_quad(ray, ray_t, self.origin, self.u, self.v)?;
+        let point = ray.at(t);
+        let material = self.material.clone();
+        let mut hit = HitRecord::new(ray, point, self.normal, t, (alpha, beta), material);
+        hit.uv_density = self.uv_density;
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let a = Aabb::from_points(self.origin, self.origin + self.u + self.v);
+        let b = Aabb::from_points(self.origin + self.u, self.origin + self.v);
+        a.union(b)
+    }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        if self.material.is_emissive() {
+            lights.push(Arc::new(QuadLight::new(self.origin, self.u, self.v)));
+        }
+    }
+}
+
+/// Intersects the quad spanned by `u` and `v` from `origin`, returning the ray
+/// parameter and the hit's coordinates along the two edges.
+pub fn intersect_quad(
+    ray: &Ray,
+    ray_t: Interval,
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+) -> Option<(f64, f64, f64)> {
+    let n = u.cross(v);
+    let denom = n.dot(ray.direction);
+    if denom.abs() < 1e-12 * n.len() * ray.direction.len() {
+        return None;
+    }
+    let t = n.dot(origin - ray.origin) / denom;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    // Project the hit's offset onto the edges through their cross products.
+    let planar = ray.at(t) - origin;
+    let w = n / n.len_squared();
+    let alpha = w.dot(planar.cross(v));
+    let beta = w.dot(u.cross(planar));
+    if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+        return None;
+    }
+    Some((t, alpha, beta))
+}
+
+/// Box with opposite corners `a` and `b`, made of six outward facing quads.
+pub fn make_box(a: Vec3, b: Vec3, material: Arc<dyn Material>) -> HitWorld {
+    let min = Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+    let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+    let mut sides = HitWorld::new();
+    let mut side = |origin, u, v| sides.push(Quad::new(origin, u, v, material.clone()));
+    side(Vec3::new(min.x, min.y, max.z), dx, dy); // front
+    side(Vec3::new(max.x, min.y, max.z), -dz, dy); // right
+    side(Vec3::new(max.x, min.y, min.z), -dx, dy); // back
+    side(Vec3::new(min.x, min.y, min.z), dz, dy); // left
+    side(Vec3::new(min.x, max.y, max.z), dx, -dz); // top
+    side(Vec3::new(min.x, min.y, min.z), dx, dz); // bottom
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::LambertianMaterial;
+
+    #[test]
+    fn hits_inside_edges() {
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let quad = Quad::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            material,
+        );
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let ray = Ray::new(Vec3::new(1.5, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = quad.hit(&ray, ray_t).unwrap();
+        assert_eq!((hit.t, hit.u, hit.v), (1.0, 0.75, 0.25));
+        assert!(hit.front_face);
+        let ray = Ray::new(Vec3::new(2.5, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad.hit(&ray, ray_t).is_none());
+    }
+
+    #[test]
+    fn box_faces_outward() {
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        let cube = make_box(Vec3::splat(-1.0), Vec3::splat(1.0), material);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        for direction in [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ] {
+            let ray = Ray::new(direction * 3.0, -direction);
+            let hit = cube.hit(&ray, ray_t).unwrap();
+            assert_eq!(hit.t, 2.0);
+            assert!(hit.front_face, "face towards {direction:?}");
+        }
+    }
+}
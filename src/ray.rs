@@ -1,17 +1,58 @@
 use crate::vec3::Vec3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// Footprint of the pixel the ray was traced for, used to filter textures.
+    pub cone: Cone,
+    /// Moment within the shutter interval at which the ray was traced.
+    pub time: f64,
+    /// Seed for the random choices made along the ray, such as where it
+    /// scatters inside a medium.
+    pub seed: u64,
 }
 
 impl Ray {
+    /// Ray at time zero with no footprint, for which textures are sampled at
+    /// full detail.
     pub fn new(origin: Vec3, direction: Vec3) -> Self {
-        Self { origin, direction }
+        Self {
+            origin,
+            direction,
+            cone: Cone::default(),
+            time: 0.0,
+            seed: 0,
+        }
+    }
+
+    pub fn with_cone(self, cone: Cone) -> Self {
+        Self { cone, ..self }
+    }
+
+    pub fn with_time(self, time: f64) -> Self {
+        Self { time, ..self }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
     }
 
     pub fn at(&self, t: f64) -> Vec3 {
         self.origin + t * self.direction
     }
+
+    /// Width of the ray's cone where it reaches parameter `t`.
+    pub fn width_at(&self, t: f64) -> f64 {
+        self.cone.width + self.cone.spread * t * self.direction.len()
+    }
+}
+
+/// Cone around a ray that approximates the region a pixel sees.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Cone {
+    /// Width at the ray's origin.
+    pub width: f64,
+    /// Growth in width per unit of distance travelled.
+    pub spread: f64,
 }
@@ -0,0 +1,483 @@
+//! Deterministic random numbers for rendering.
+//!
+//! Every sample of every pixel draws from its own sequence, determined by
+//! the render's seed, the pixel and the sample index, so an image depends
+//! only on the seed and not on how the work was split between threads.
+//!
+//! Samplers hand out the dimensions of a sample one or two at a time, in
+//! the order the camera and integrator ask for them. Low-discrepancy
+//! samplers spread the values of each dimension evenly across the samples
+//! of a pixel, which converges faster than independent random numbers.
+
+/// Source of the random numbers for samples of pixels.
+pub trait Sampler {
+    /// Moves to sample `index` of `pixel`, starting again from its first
+    /// dimension.
+    fn start(&mut self, pixel: (u32, u32), index: u32);
+
+    /// Next dimension of the sample, in [0, 1).
+    fn get_1d(&mut self) -> f64;
+
+    /// Next two dimensions of the sample, each in [0, 1), spread evenly
+    /// over the square together.
+    fn get_2d(&mut self) -> (f64, f64);
+
+    /// Random bits for choices that don't benefit from being spread evenly,
+    /// such as the seeds of rays.
+    fn next_u64(&mut self) -> u64;
+
+    /// Index below `n`, from the next dimension.
+    fn index(&mut self, n: usize) -> usize {
+        ((self.get_1d() * n as f64) as usize).min(n - 1)
+    }
+}
+
+/// Kind of [`Sampler`] to render with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+}
+
+impl SamplerKind {
+    /// Sampler for a render seeded with `seed` that takes `samples` samples
+    /// per pixel. Samples past that count are still valid, but only spread
+    /// evenly among themselves.
+    pub fn build(self, seed: u64, samples: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(seed, samples)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+/// Independent uniform random numbers.
+#[derive(Debug, Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    random: Random,
+}
+
+impl IndependentSampler {
+    /// Sampler for a render seeded with `seed`. Until the first
+    /// [`Sampler::start`] it draws from a stream seeded with `seed` itself.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            random: Random::new(seed),
+        }
+    }
+
+    /// Sampler drawing from a stream of its own for each `key`, so that
+    /// separate uses of one seed don't draw the same numbers.
+    pub fn keyed(seed: u64, key: u64) -> Self {
+        Self::new(mix(seed ^ mix(key)))
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start(&mut self, pixel: (u32, u32), index: u32) {
+        self.random = Random::new(mix(pixel_hash(self.seed, pixel) ^ index as u64));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.random.get_1d()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.random.get_1d(), self.random.get_1d())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.random.next_u64()
+    }
+}
+
+/// Jittered samples, one in each of a grid of strata per dimension, with
+/// the strata shuffled independently for every dimension.
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    seed: u64,
+    samples: u32,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+    random: Random,
+}
+
+impl StratifiedSampler {
+    /// # Panics
+    ///
+    /// If `samples` is zero.
+    pub fn new(seed: u64, samples: u32) -> Self {
+        assert!(samples > 0, "stratified sampler needs at least one sample");
+        Self {
+            seed,
+            samples,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+            random: Random::new(seed),
+        }
+    }
+
+    /// Position of the current sample among `strata` shuffled strata of the
+    /// next dimension.
+    fn stratum(&mut self, strata: u32) -> u32 {
+        // Each round of `samples` samples is shuffled separately.
+        let round = self.index / self.samples;
+        let key = mix(mix(self.pixel ^ self.dimension) ^ round as u64);
+        self.dimension += 1;
+        permute(self.index % self.samples, strata, key as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel_hash(self.seed, pixel);
+        self.index = index;
+        self.dimension = 0;
+        self.random = Random::new(mix(self.pixel ^ index as u64));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let stratum = self.stratum(self.samples);
+        (stratum as f64 + self.random.get_1d()) / self.samples as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let nx = (self.samples as f64).sqrt().ceil() as u32;
+        let ny = self.samples.div_ceil(nx);
+        let stratum = self.stratum(nx * ny);
+        let (x, y) = (stratum % nx, stratum / nx);
+        (
+            (x as f64 + self.random.get_1d()) / nx as f64,
+            (y as f64 + self.random.get_1d()) / ny as f64,
+        )
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.random.next_u64()
+    }
+}
+
+/// Bases of the Halton sequence's dimensions. Later dimensions fall back to
+/// independent random numbers.
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// The Halton sequence, Owen scrambled differently in every dimension of
+/// every pixel.
+#[derive(Debug, Clone)]
+pub struct HaltonSampler {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: usize,
+    random: Random,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+            random: Random::new(seed),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel_hash(self.seed, pixel);
+        self.index = index;
+        self.dimension = 0;
+        self.random = Random::new(mix(self.pixel ^ index as u64));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let Some(&base) = PRIMES.get(self.dimension) else {
+            return self.random.get_1d();
+        };
+        let seed = mix(self.pixel ^ self.dimension as u64);
+        self.dimension += 1;
+        scrambled_radical_inverse(base, self.index as u64, seed)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.get_1d(), self.get_1d())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.random.next_u64()
+    }
+}
+
+/// The first two dimensions of the Sobol sequence, Owen scrambled with a
+/// different seed for every pair of dimensions of every pixel, after Burley,
+/// "Practical Hash-based Owen Scrambling".
+#[derive(Debug, Clone)]
+pub struct SobolSampler {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+    random: Random,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+            random: Random::new(seed),
+        }
+    }
+
+    /// Sobol index shuffled for the next dimension, and the seed scrambling
+    /// its values.
+    fn next_point(&mut self) -> (u32, u32) {
+        let seed = mix(self.pixel ^ self.dimension) as u32;
+        self.dimension += 1;
+        (nested_uniform_scramble(self.index, seed), seed)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel_hash(self.seed, pixel);
+        self.index = index;
+        self.dimension = 0;
+        self.random = Random::new(mix(self.pixel ^ index as u64));
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, seed) = self.next_point();
+        let x = nested_uniform_scramble(index.reverse_bits(), hash_combine(seed, 0));
+        x as f64 / TWO_TO_32
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, seed) = self.next_point();
+        let x = nested_uniform_scramble(index.reverse_bits(), hash_combine(seed, 0));
+        let y = nested_uniform_scramble(sobol_second(index), hash_combine(seed, 1));
+        (x as f64 / TWO_TO_32, y as f64 / TWO_TO_32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.random.next_u64()
+    }
+}
+
+const TWO_TO_32: f64 = 4_294_967_296.0;
+
+/// Generator matrix of the second Sobol dimension, one column per bit of the
+/// index.
+const SOBOL_SECOND: [u32; 32] = {
+    let mut columns = [0; 32];
+    columns[0] = 1 << 31;
+    let mut i = 1;
+    while i < 32 {
+        columns[i] = columns[i - 1] ^ (columns[i - 1] >> 1);
+        i += 1;
+    }
+    columns
+};
+
+/// Second dimension of the Sobol sequence as a 32-bit fraction. The first
+/// is just the index with its bits reversed.
+fn sobol_second(index: u32) -> u32 {
+    (0..32)
+        .filter(|bit| index >> bit & 1 == 1)
+        .fold(0, |x, bit| x ^ SOBOL_SECOND[bit])
+}
+
+/// Owen scrambling of a 32-bit fraction, flipping each bit depending on the
+/// bits above it.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn hash_combine(seed: u32, v: u32) -> u32 {
+    seed ^ (v
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2)
+        .wrapping_add(0x9e37_79b9))
+}
+
+/// Digits of `index` in `base`, mirrored around the radix point, with each
+/// digit permuted depending on the digits before it.
+fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let (mut reversed, mut scale) = (0, 1.0);
+    // Trailing zero digits are permuted too, down to a resolution finer
+    // than any sample needs.
+    while scale > 1e-7 {
+        let key = mix(seed ^ reversed) as u32;
+        let digit = permute((index % base) as u32, base as u32, key) as u64;
+        reversed = reversed * base + digit;
+        scale *= inv_base;
+        index /= base;
+    }
+    (reversed as f64 * scale).min(1.0 - f64::EPSILON / 2.0)
+}
+
+/// Element `i` of a pseudo-random permutation of `0..n` chosen by `key`,
+/// from Kensler, "Correlated Multi-Jittered Sampling".
+fn permute(mut i: u32, n: u32, key: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= key;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= key >> 16;
+        i ^= (i & w) >> 4;
+        i ^= key >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= key >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | key >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            return (i + key) % n;
+        }
+    }
+}
+
+/// SplitMix64 stream of random numbers.
+#[derive(Debug, Clone)]
+struct Random {
+    state: u64,
+}
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.state)
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        to_unit(self.next_u64())
+    }
+}
+
+fn pixel_hash(seed: u64, (i, j): (u32, u32)) -> u64 {
+    mix(mix(seed) ^ ((j as u64) << 32 | i as u64))
+}
+
+/// Number in [0, 1) from the top 53 bits of `x`.
+fn to_unit(x: u64) -> f64 {
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Scrambles the bits of `x`, taking nearby inputs to unrelated outputs.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+
+    #[test]
+    fn sequences_are_reproducible_and_distinct() {
+        for kind in KINDS {
+            let draw = |pixel, index| {
+                let mut sampler = kind.build(7, 16);
+                sampler.start(pixel, index);
+                [sampler.get_1d(), sampler.get_2d().0, sampler.get_2d().1]
+            };
+            assert_eq!(draw((3, 4), 5), draw((3, 4), 5), "{kind:?}");
+            assert_ne!(draw((3, 4), 5), draw((4, 3), 5), "{kind:?}");
+            assert_ne!(draw((3, 4), 5), draw((3, 4), 6), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn spreads_samples_evenly() {
+        // 64 samples put exactly one point in every cell of an 8 by 8 grid,
+        // in every dimension after the first few.
+        for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+            let mut sampler = kind.build(3, 64);
+            let mut cells = vec![[0; 64]; 4];
+            for index in 0..64 {
+                sampler.start((5, 9), index);
+                for cells in &mut cells {
+                    let (u, v) = sampler.get_2d();
+                    cells[(u * 8.0) as usize * 8 + (v * 8.0) as usize] += 1;
+                }
+            }
+            assert!(cells.iter().flatten().all(|&n| n == 1), "{kind:?}");
+        }
+
+        // The Halton sequence's first dimension is evenly spaced, and stays
+        // so when scrambled.
+        let mut sampler = SamplerKind::Halton.build(3, 64);
+        let mut cells = [0; 64];
+        for index in 0..64 {
+            sampler.start((5, 9), index);
+            cells[(sampler.get_1d() * 64.0) as usize] += 1;
+        }
+        assert!(cells.iter().all(|&n| n == 1));
+    }
+
+    #[test]
+    fn values_stay_in_unit_interval() {
+        for kind in KINDS {
+            let mut sampler = kind.build(11, 5);
+            for index in 0..40 {
+                sampler.start((index, 2), index);
+                for _ in 0..50 {
+                    let (u, v) = sampler.get_2d();
+                    let w = sampler.get_1d();
+                    assert!([u, v, w].iter().all(|x| (0.0..1.0).contains(x)));
+                }
+            }
+        }
+    }
+}
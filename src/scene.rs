@@ -0,0 +1,1391 @@
+//! Text scene descriptions.
+//!
+//! A scene is a list of blocks, each made of a kind, optional arguments and
+//! a braced list of `key value...` entries separated by newlines or `;`:
+//!
+//! ```text
+//! # Comments run to the end of the line.
+//! render { width 400; aspect_ratio 16/9; samples 100; max_depth 50; integrator path }
+//! camera {
+//!     lookfrom 13 2 3
+//!     lookat 0 0 0
+//! }
+//! background { bottom 1 1 1; top 0.5 0.7 1 }
+//! texture checks checker { scale 0.3; even 0.2 0.3 0.1; odd 0.9 0.9 0.9 }
+//! texture earth image { file "earth.png"; address wrap; filter trilinear }
+//! texture stone marble { scale 4; color 1 1 1; seed 7 }
+//! material ground lambertian { albedo checks }
+//! plane { point 0 0 0; normal 0 1 0; material ground }
+//! sphere { center 0 1 0; radius 1; material ground }
+//! quad { origin -1 3 -1; u 2 0 0; v 0 0 2; material ground }
+//! box { min 2 0 0; max 3 1 1; material ground }
+//! disk { center 0 0.01 3; normal 0 1 0; radius 0.5; material ground }
+//! mesh { file "bunny.obj" }
+//! ```
+//!
+//! Shapes also take `translate x y z`, `rotate x y z degrees` and
+//! `scale s` or `scale x y z` entries, applied in the order given. Meshes
+//! loaded from the same file share their geometry.
+//!
+//! Shapes move while the camera's shutter is open, between `shutter_open`
+//! and `shutter_close`. A sphere with `center1` moves from `center` at time
+//! 0 to `center1` at time 1. Any shape can be keyframed with `time t`
+//! entries, each followed by the transform at that time; transforms before
+//! the first `time` apply throughout. In between, shapes turn the short way
+//! from one keyframe's rotation to the next:
+//!
+//! ```text
+//! camera { lookfrom 0 2 10; shutter_open 0; shutter_close 1 }
+//! sphere { center 0 1 0; center1 0 1.5 0; radius 1; material ground }
+//! box { min 0 0 0; max 1 1 1; material ground; time 0; time 1; rotate 0 1 0 30 }
+//! ```
+//!
+//! Wherever a material takes a color, it also accepts the name of a texture.
+//!
+//! A sphere or box with a `density` is filled with smoke or fog instead,
+//! scattering with its material, typically `isotropic` or
+//! `henyey_greenstein`:
+//!
+//! ```text
+//! material smoke henyey_greenstein { albedo 0.8 0.8 0.8; g 0.5 }
+//! sphere { center 0 1 0; radius 1; material smoke; density 0.5 }
+//! ```
+//!
+//! Volumes of varying density come from a voxel grid file (see
+//! [`crate::grid`]) or from noise filling a box. Their `absorption` and
+//! `scattering` coefficients are per unit of density and of distance in the
+//! volume's own space, before any `scale`. Absorbed light is replaced by
+//! `emission`, and `material` sets the phase function, which is isotropic
+//! by default:
+//!
+//! ```text
+//! volume grid { file "smoke.vol"; absorption 0.5; scattering 4; material smoke }
+//! volume noise { min -2 0 -2; max 2 3 2; scale 1.5; octaves 4; scattering 2 }
+//! ```
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    aabb::Aabb,
+    background::Background,
+    bvh::Bvh,
+    camera::CameraSettings,
+    disk::Disk,
+    filter::FilterKind,
+    grid::VoxelGrid,
+    hit::{HitTarget, HitWorld},
+    instance::Instance,
+    integrator::{IntegratorKind, IntegratorSettings},
+    material::{
+        DielectricMaterial, DiffuseLight, HenyeyGreenstein, Isotropic, LambertianMaterial,
+        Material, MetalMaterial,
+    },
+    medium::{ConstantMedium, HeterogeneousMedium, NoiseDensity},
+    mesh::TriangleMesh,
+    obj,
+    perlin::Perlin,
+    plane::Plane,
+    quad::{Quad, make_box},
+    sampler::SamplerKind,
+    sphere::Sphere,
+    texture::{
+        AddressMode, CheckerTexture, ImageTexture, MarbleTexture, NoiseTexture, SolidColor,
+        Texture, TextureFilter, TurbulenceTexture, WoodTexture,
+    },
+    transform::Transform,
+    vec3::Vec3,
+};
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{line}:{column}: {message}", path.display()),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+pub struct Scene {
+    pub camera: CameraSettings,
+    pub integrator: IntegratorSettings,
+    pub background: Background,
+    pub world: HitWorld,
+}
+
+/// Loads a scene file. Relative paths inside it are resolved against the
+/// directory containing it.
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_scene(&source, path)
+}
+
+/// Parses the contents of a scene file. `path` is used for errors and to
+/// resolve relative paths.
+pub fn parse_scene(source: &str, path: &Path) -> Result<Scene, SceneError> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let into_error = |e: ParseError| SceneError::Parse {
+        path: path.to_path_buf(),
+        line: e.line,
+        column: e.column,
+        message: e.message,
+    };
+
+    let tokens = tokenize(source).map_err(into_error)?;
+    let blocks = Parser::new(tokens).blocks().map_err(into_error)?;
+    let mut builder = SceneBuilder {
+        dir,
+        camera: CameraSettings::default(),
+        integrator: IntegratorSettings::default(),
+        background: Background::default(),
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        meshes: HashMap::new(),
+        world: HitWorld::new(),
+    };
+    for block in &blocks {
+        builder.block(block).map_err(into_error)?;
+    }
+    Ok(Scene {
+        camera: builder.camera,
+        integrator: builder.integrator,
+        background: builder.background,
+        world: builder.world,
+    })
+}
+
+struct ParseError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Number(f64),
+    Str(String),
+    OpenBrace,
+    CloseBrace,
+    /// A newline or `;`.
+    Separator,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) => write!(f, "`{word}`"),
+            Self::Number(n) => write!(f, "`{n}`"),
+            Self::Str(s) => write!(f, "{s:?}"),
+            Self::OpenBrace => write!(f, "`{{`"),
+            Self::CloseBrace => write!(f, "`}}`"),
+            Self::Separator => write!(f, "end of entry"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut chars = line.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            let column = line[..start].chars().count() + 1;
+            let token = |kind| Token {
+                kind,
+                line: line_number,
+                column,
+            };
+            let error = |message: String| ParseError {
+                line: line_number,
+                column,
+                message,
+            };
+
+            if c == '#' {
+                break;
+            }
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+
+            let kind = match c {
+                '{' => {
+                    chars.next();
+                    TokenKind::OpenBrace
+                }
+                '}' => {
+                    chars.next();
+                    TokenKind::CloseBrace
+                }
+                ';' => {
+                    chars.next();
+                    TokenKind::Separator
+                }
+                '"' => {
+                    chars.next();
+                    let mut s = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, c)) => s.push(c),
+                            None => return Err(error("unterminated string".to_string())),
+                        }
+                    }
+                    TokenKind::Str(s)
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => {
+                    let mut word = String::new();
+                    while let Some(&(_, c)) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || c == '_') {
+                            break;
+                        }
+                        word.push(c);
+                        chars.next();
+                    }
+                    TokenKind::Word(word)
+                }
+                c if c.is_ascii_digit() || matches!(c, '-' | '+' | '.') => {
+                    let mut text = String::new();
+                    while let Some(&(_, c)) = chars.peek() {
+                        if !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.' | '/')) {
+                            break;
+                        }
+                        text.push(c);
+                        chars.next();
+                    }
+                    let number = parse_number(&text)
+                        .ok_or_else(|| error(format!("invalid number `{text}`")))?;
+                    TokenKind::Number(number)
+                }
+                c => return Err(error(format!("unexpected character `{c}`"))),
+            };
+            tokens.push(token(kind));
+        }
+
+        if tokens
+            .last()
+            .is_some_and(|t| t.kind != TokenKind::Separator)
+        {
+            tokens.push(Token {
+                kind: TokenKind::Separator,
+                line: line_number,
+                column: line.chars().count() + 1,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a number, allowing ratios like `16/9`.
+fn parse_number(text: &str) -> Option<f64> {
+    match text.split_once('/') {
+        Some((n, d)) => Some(n.parse::<f64>().ok()? / d.parse::<f64>().ok()?),
+        None => text.parse().ok(),
+    }
+}
+
+struct Block {
+    kind: Token,
+    args: Vec<Token>,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    key: Token,
+    values: Vec<Token>,
+    /// Where the entry ends, for errors about missing values.
+    end: Token,
+}
+
+struct Parser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+    last: Token,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        let last = tokens.last().cloned().unwrap_or(Token {
+            kind: TokenKind::Separator,
+            line: 1,
+            column: 1,
+        });
+        Self {
+            tokens: tokens.into_iter().peekable(),
+            last,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .tokens
+            .next_if(|t| t.kind == TokenKind::Separator)
+            .is_some()
+        {}
+    }
+
+    fn next(&mut self, expected: &str) -> Result<Token, ParseError> {
+        self.tokens.next().ok_or_else(|| {
+            self.last
+                .error(format!("expected {expected}, found end of file"))
+        })
+    }
+
+    fn peek(&mut self, expected: &str) -> Result<Token, ParseError> {
+        match self.tokens.peek() {
+            Some(token) => Ok(token.clone()),
+            None => Err(self
+                .last
+                .error(format!("expected {expected}, found end of file"))),
+        }
+    }
+
+    fn blocks(&mut self) -> Result<Vec<Block>, ParseError> {
+        let mut blocks = Vec::new();
+        loop {
+            self.skip_separators();
+            if self.tokens.peek().is_none() {
+                return Ok(blocks);
+            }
+            blocks.push(self.block()?);
+        }
+    }
+
+    fn block(&mut self) -> Result<Block, ParseError> {
+        let kind = self.next("a block")?;
+        if !matches!(kind.kind, TokenKind::Word(_)) {
+            return Err(kind.error(format!("expected a block, found {}", kind.kind)));
+        }
+
+        let mut args = Vec::new();
+        loop {
+            let token = self.next("`{`")?;
+            match token.kind {
+                TokenKind::OpenBrace => break,
+                TokenKind::Word(_) | TokenKind::Number(_) | TokenKind::Str(_) => args.push(token),
+                _ => return Err(token.error(format!("expected `{{`, found {}", token.kind))),
+            }
+        }
+
+        let mut entries = Vec::new();
+        loop {
+            self.skip_separators();
+            let key = self.next("`}`")?;
+            match key.kind {
+                TokenKind::CloseBrace => break,
+                TokenKind::Word(_) => {}
+                _ => return Err(key.error(format!("expected a key, found {}", key.kind))),
+            }
+
+            let mut values = Vec::new();
+            let end = loop {
+                let token = self.peek("`}`")?;
+                match token.kind {
+                    // Leave the brace for the outer loop to consume.
+                    TokenKind::CloseBrace => break token,
+                    TokenKind::Separator => {
+                        self.tokens.next();
+                        break token;
+                    }
+                    TokenKind::OpenBrace => return Err(token.error("unexpected `{`")),
+                    _ => {
+                        self.tokens.next();
+                        values.push(token);
+                    }
+                }
+            };
+            entries.push(Entry { key, values, end });
+        }
+
+        Ok(Block {
+            kind,
+            args,
+            entries,
+        })
+    }
+}
+
+impl Block {
+    fn name(&self) -> &str {
+        word(&self.kind)
+    }
+
+    /// Checks that the block has exactly `N` word arguments.
+    fn args<const N: usize>(&self, usage: &str) -> Result<[&str; N], ParseError> {
+        let wrong = || {
+            self.kind
+                .error(format!("expected `{} {usage} {{ ... }}`", self.name()))
+        };
+        if self.args.len() != N {
+            return Err(wrong());
+        }
+        let mut out = [""; N];
+        for (slot, arg) in out.iter_mut().zip(&self.args) {
+            let TokenKind::Word(w) = &arg.kind else {
+                return Err(arg.error(format!("expected a name, found {}", arg.kind)));
+            };
+            *slot = w;
+        }
+        Ok(out)
+    }
+
+    fn missing(&self, key: &str) -> ParseError {
+        self.kind
+            .error(format!("`{}` is missing `{key}`", self.name()))
+    }
+}
+
+impl Entry {
+    fn key(&self) -> &str {
+        word(&self.key)
+    }
+
+    fn unknown(&self, block: &Block) -> ParseError {
+        self.key.error(format!(
+            "unknown key `{}` in `{}`",
+            self.key(),
+            block.name()
+        ))
+    }
+
+    fn value(&self, index: usize, count: usize) -> Result<&Token, ParseError> {
+        if self.values.len() != count {
+            let token = self.values.get(count).unwrap_or(&self.end);
+            let s = if count == 1 { "" } else { "s" };
+            return Err(token.error(format!(
+                "`{}` takes {count} value{s}, found {}",
+                self.key(),
+                self.values.len()
+            )));
+        }
+        Ok(&self.values[index])
+    }
+
+    fn number_at(&self, index: usize, count: usize) -> Result<f64, ParseError> {
+        let token = self.value(index, count)?;
+        match token.kind {
+            TokenKind::Number(n) => Ok(n),
+            ref kind => Err(token.error(format!("expected a number, found {kind}"))),
+        }
+    }
+
+    fn number(&self) -> Result<f64, ParseError> {
+        self.number_at(0, 1)
+    }
+
+    fn positive(&self) -> Result<f64, ParseError> {
+        let n = self.number()?;
+        if n > 0.0 {
+            Ok(n)
+        } else {
+            Err(self.values[0].error(format!("`{}` must be positive", self.key())))
+        }
+    }
+
+    fn non_negative(&self) -> Result<f64, ParseError> {
+        let n = self.number()?;
+        if n >= 0.0 {
+            Ok(n)
+        } else {
+            Err(self.values[0].error(format!("`{}` must not be negative", self.key())))
+        }
+    }
+
+    /// Like `integer`, but allows zero.
+    fn count<T: TryFrom<u64>>(&self) -> Result<T, ParseError> {
+        let n = self.number()?;
+        let token = &self.values[0];
+        if n < 0.0 || n.fract() != 0.0 {
+            return Err(token.error(format!("`{}` must be a whole number", self.key())));
+        }
+        T::try_from(n as u64).map_err(|_| token.error(format!("`{}` is too large", self.key())))
+    }
+
+    fn integer<T: TryFrom<u64>>(&self) -> Result<T, ParseError> {
+        let n = self.number()?;
+        let token = &self.values[0];
+        if n < 1.0 || n.fract() != 0.0 {
+            return Err(token.error(format!("`{}` must be a positive whole number", self.key())));
+        }
+        T::try_from(n as u64).map_err(|_| token.error(format!("`{}` is too large", self.key())))
+    }
+
+    fn vec3(&self) -> Result<Vec3, ParseError> {
+        Ok(Vec3::new(
+            self.number_at(0, 3)?,
+            self.number_at(1, 3)?,
+            self.number_at(2, 3)?,
+        ))
+    }
+
+    /// Reads a `translate`, `rotate` or `scale` entry of a shape.
+    fn transform(&self, block: &Block) -> Result<Transform, ParseError> {
+        match self.key() {
+            "translate" => Ok(Transform::translate(self.vec3()?)),
+            "rotate" => {
+                let axis = Vec3::new(
+                    self.number_at(0, 4)?,
+                    self.number_at(1, 4)?,
+                    self.number_at(2, 4)?,
+                );
+                if axis.is_near_zero() {
+                    return Err(self.values[0].error("rotation axis must not be zero"));
+                }
+                Ok(Transform::rotate(axis, self.number_at(3, 4)?))
+            }
+            "scale" => {
+                let factors = if self.values.len() == 1 {
+                    Vec3::splat(self.number()?)
+                } else {
+                    self.vec3()?
+                };
+                if factors.x == 0.0 || factors.y == 0.0 || factors.z == 0.0 {
+                    return Err(self.values[0].error("scale factors must not be zero"));
+                }
+                Ok(Transform::scale(factors))
+            }
+            _ => Err(self.unknown(block)),
+        }
+    }
+
+    /// Reads a vector that has to have a direction.
+    fn direction(&self) -> Result<Vec3, ParseError> {
+        let v = self.vec3()?;
+        if v.is_near_zero() {
+            return Err(self.values[0].error(format!("`{}` must not be zero", self.key())));
+        }
+        Ok(v)
+    }
+
+    fn word(&self) -> Result<&str, ParseError> {
+        let token = self.value(0, 1)?;
+        match &token.kind {
+            TokenKind::Word(w) => Ok(w),
+            kind => Err(token.error(format!("expected a name, found {kind}"))),
+        }
+    }
+
+    fn string(&self) -> Result<&str, ParseError> {
+        let token = self.value(0, 1)?;
+        match &token.kind {
+            TokenKind::Str(s) => Ok(s),
+            kind => Err(token.error(format!("expected a quoted string, found {kind}"))),
+        }
+    }
+}
+
+/// Placement of a shape, built from its transform entries. Entries before
+/// the first `time` apply at every time, and each `time t` starts a keyframe
+/// whose own entries follow it.
+#[derive(Default)]
+struct Motion {
+    common: Transform,
+    keyframes: Vec<(f64, Transform)>,
+}
+
+impl Motion {
+    fn entry(&mut self, entry: &Entry, block: &Block) -> Result<(), ParseError> {
+        if entry.key() == "time" {
+            let time = entry.number()?;
+            if self.keyframes.last().is_some_and(|&(last, _)| time <= last) {
+                return Err(entry.values[0].error("keyframe times must increase"));
+            }
+            self.keyframes.push((time, self.common));
+            return Ok(());
+        }
+        let transform = entry.transform(block)?;
+        match self.keyframes.last_mut() {
+            Some((_, last)) => *last = last.then(transform),
+            None => self.common = self.common.then(transform),
+        }
+        Ok(())
+    }
+
+    fn is_identity(&self) -> bool {
+        self.keyframes.is_empty() && self.common == Transform::IDENTITY
+    }
+
+    fn instance(self, object: Arc<dyn HitTarget>) -> Instance {
+        if self.keyframes.is_empty() {
+            Instance::new(object, self.common)
+        } else {
+            Instance::keyframed(object, self.keyframes)
+        }
+    }
+}
+
+fn word(token: &Token) -> &str {
+    match &token.kind {
+        TokenKind::Word(w) => w,
+        _ => unreachable!("blocks and keys are always words"),
+    }
+}
+
+struct SceneBuilder<'a> {
+    dir: &'a Path,
+    camera: CameraSettings,
+    integrator: IntegratorSettings,
+    background: Background,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    /// Meshes by path, so that files used more than once are loaded and
+    /// stored once.
+    meshes: HashMap<PathBuf, Arc<dyn HitTarget>>,
+    world: HitWorld,
+}
+
+impl SceneBuilder<'_> {
+    fn block(&mut self, block: &Block) -> Result<(), ParseError> {
+        match block.name() {
+            "render" => self.render(block),
+            "camera" => self.camera(block),
+            "background" => self.background(block),
+            "texture" => self.texture(block),
+            "material" => self.material(block),
+            "sphere" => self.sphere(block),
+            "quad" => self.quad(block),
+            "box" => self.cuboid(block),
+            "plane" => self.plane(block),
+            "disk" => self.disk(block),
+            "mesh" => self.mesh(block),
+            "volume" => self.volume(block),
+            name => Err(block.kind.error(format!("unknown block `{name}`"))),
+        }
+    }
+
+    fn render(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let camera = &mut self.camera;
+        let integrator = &mut self.integrator;
+        for entry in &block.entries {
+            match entry.key() {
+                "width" => camera.image_width = entry.integer()?,
+                "aspect_ratio" => camera.aspect_ratio = entry.positive()?,
+                "samples" => camera.samples_per_pixel = entry.integer()?,
+                "min_samples" => camera.min_samples = entry.integer()?,
+                "adaptive_threshold" => camera.adaptive_threshold = Some(entry.positive()?),
+                "max_depth" => integrator.max_depth = entry.integer()?,
+                "roulette_depth" => integrator.roulette_depth = entry.count()?,
+                "integrator" => {
+                    integrator.kind = match entry.word()? {
+                        "path" => IntegratorKind::Path,
+                        "whitted" => IntegratorKind::Whitted,
+                        "ao" => IntegratorKind::AmbientOcclusion,
+                        "normals" => IntegratorKind::Normals,
+                        "albedo" => IntegratorKind::Albedo,
+                        kind => {
+                            return Err(entry.values[0].error(format!(
+                                "unknown integrator `{kind}`, expected `path`, `whitted`, `ao`, `normals` or `albedo`"
+                            )));
+                        }
+                    }
+                }
+                "ao_distance" => integrator.ao_distance = entry.positive()?,
+                "seed" => camera.seed = entry.count()?,
+                "filter" => {
+                    camera.filter = match entry.word()? {
+                        "box" => FilterKind::Box,
+                        "tent" => FilterKind::Tent,
+                        "gaussian" => FilterKind::Gaussian,
+                        "mitchell" => FilterKind::Mitchell,
+                        "lanczos" => FilterKind::Lanczos,
+                        kind => {
+                            return Err(entry.values[0].error(format!(
+                                "unknown filter `{kind}`, expected `box`, `tent`, `gaussian`, `mitchell` or `lanczos`"
+                            )));
+                        }
+                    }
+                }
+                "filter_radius" => camera.filter_radius = Some(entry.positive()?),
+                "sampler" => {
+                    camera.sampler = match entry.word()? {
+                        "independent" => SamplerKind::Independent,
+                        "stratified" => SamplerKind::Stratified,
+                        "halton" => SamplerKind::Halton,
+                        "sobol" => SamplerKind::Sobol,
+                        kind => {
+                            return Err(entry.values[0].error(format!(
+                                "unknown sampler `{kind}`, expected `independent`, `stratified`, `halton` or `sobol`"
+                            )));
+                        }
+                    }
+                }
+                _ => return Err(entry.unknown(block)),
+            }
+        }
+        Ok(())
+    }
+
+    fn camera(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let camera = &mut self.camera;
+        for entry in &block.entries {
+            match entry.key() {
+                "vfov" => camera.vfov = entry.positive()?,
+                "lookfrom" => camera.lookfrom = entry.vec3()?,
+                "lookat" => camera.lookat = entry.vec3()?,
+                "vup" => camera.vup = entry.vec3()?,
+                "defocus_angle" => camera.defocus_angle = entry.number()?,
+                "focus_dist" => camera.focus_dist = entry.positive()?,
+                "shutter_open" => camera.shutter_open = entry.number()?,
+                "shutter_close" => camera.shutter_close = entry.number()?,
+                _ => return Err(entry.unknown(block)),
+            }
+        }
+        Ok(())
+    }
+
+    fn background(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let mut color = None;
+        let mut bottom = None;
+        let mut top = None;
+        for entry in &block.entries {
+            match entry.key() {
+                "color" => color = Some(entry.vec3()?),
+                "bottom" => bottom = Some(entry.vec3()?),
+                "top" => top = Some(entry.vec3()?),
+                _ => return Err(entry.unknown(block)),
+            }
+        }
+        self.background = match (color, bottom, top) {
+            (Some(color), None, None) => Background::Solid(color),
+            (None, Some(bottom), Some(top)) => Background::Gradient { bottom, top },
+            _ => {
+                return Err(block
+                    .kind
+                    .error("`background` takes either `color` or both `bottom` and `top`"));
+            }
+        };
+        Ok(())
+    }
+
+    fn texture(&mut self, block: &Block) -> Result<(), ParseError> {
+        let [name, kind] = block.args("<name> <type>")?;
+        if self.textures.contains_key(name) {
+            return Err(block.args[0].error(format!("texture `{name}` is already defined")));
+        }
+
+        let texture: Arc<dyn Texture> = match kind {
+            "solid" => {
+                let mut color = None;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "color" => color = Some(entry.vec3()?),
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let color = color.ok_or_else(|| block.missing("color"))?;
+                Arc::new(SolidColor::new(color))
+            }
+            "checker" => {
+                let mut scale = 1.0;
+                let mut even = None;
+                let mut odd = None;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "scale" => scale = entry.positive()?,
+                        "even" => even = Some(self.lookup_texture(entry)?),
+                        "odd" => odd = Some(self.lookup_texture(entry)?),
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let even = even.ok_or_else(|| block.missing("even"))?;
+                let odd = odd.ok_or_else(|| block.missing("odd"))?;
+                Arc::new(CheckerTexture::new(scale, even, odd))
+            }
+            "noise" | "turbulence" | "marble" | "wood" => self.noise_texture(block, kind)?,
+            "image" => {
+                let mut file = None;
+                let mut address = AddressMode::default();
+                let mut filter = TextureFilter::default();
+                for entry in &block.entries {
+                    match entry.key() {
+                        "file" => file = Some((entry, entry.string()?)),
+                        "address" => {
+                            address = match entry.word()? {
+                                "wrap" => AddressMode::Wrap,
+                                "clamp" => AddressMode::Clamp,
+                                "mirror" => AddressMode::Mirror,
+                                mode => {
+                                    return Err(entry.values[0].error(format!(
+                                        "unknown address mode `{mode}`, expected `wrap`, `clamp` or `mirror`"
+                                    )));
+                                }
+                            }
+                        }
+                        "filter" => {
+                            filter = match entry.word()? {
+                                "nearest" => TextureFilter::Nearest,
+                                "bilinear" => TextureFilter::Bilinear,
+                                "trilinear" => TextureFilter::Trilinear,
+                                filter => {
+                                    return Err(entry.values[0].error(format!(
+                                        "unknown filter `{filter}`, expected `nearest`, `bilinear` or `trilinear`"
+                                    )));
+                                }
+                            }
+                        }
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let (entry, file) = file.ok_or_else(|| block.missing("file"))?;
+                let texture = ImageTexture::load(self.dir.join(file), address, filter)
+                    .map_err(|e| entry.values[0].error(format!("failed to load image: {e}")))?;
+                Arc::new(texture)
+            }
+            _ => {
+                return Err(block.args[1].error(format!("unknown texture type `{kind}`")));
+            }
+        };
+        self.textures.insert(name.to_string(), texture);
+        Ok(())
+    }
+
+    fn noise_texture(&self, block: &Block, kind: &str) -> Result<Arc<dyn Texture>, ParseError> {
+        let mut scale = 1.0;
+        let mut seed = 1;
+        let mut octaves = 7;
+        let mut color = Vec3::splat(1.0);
+        let mut light = Vec3::new(0.8, 0.6, 0.4);
+        let mut dark = Vec3::new(0.4, 0.25, 0.12);
+        for entry in &block.entries {
+            match (kind, entry.key()) {
+                (_, "scale") => scale = entry.positive()?,
+                (_, "seed") => seed = entry.integer()?,
+                ("turbulence", "octaves") => octaves = entry.integer()?,
+                ("marble", "color") => color = entry.vec3()?,
+                ("wood", "light") => light = entry.vec3()?,
+                ("wood", "dark") => dark = entry.vec3()?,
+                _ => return Err(entry.unknown(block)),
+            }
+        }
+        let noise = Perlin::new(seed);
+        Ok(match kind {
+            "noise" => Arc::new(NoiseTexture::new(noise, scale)),
+            "turbulence" => Arc::new(TurbulenceTexture::new(noise, scale, octaves)),
+            "marble" => Arc::new(MarbleTexture::new(noise, scale, color)),
+            _ => Arc::new(WoodTexture::new(noise, scale, light, dark)),
+        })
+    }
+
+    /// Reads either a color or the name of a texture.
+    fn lookup_texture(&self, entry: &Entry) -> Result<Arc<dyn Texture>, ParseError> {
+        if entry.values.len() != 1 {
+            return Ok(Arc::new(SolidColor::new(entry.vec3()?)));
+        }
+        let name = entry.word()?;
+        self.textures
+            .get(name)
+            .cloned()
+            .ok_or_else(|| entry.values[0].error(format!("unknown texture `{name}`")))
+    }
+
+    fn material(&mut self, block: &Block) -> Result<(), ParseError> {
+        let [name, kind] = block.args("<name> <type>")?;
+        if self.materials.contains_key(name) {
+            return Err(block.args[0].error(format!("material `{name}` is already defined")));
+        }
+
+        let material: Arc<dyn Material> = match kind {
+            "lambertian" => {
+                let mut albedo = None;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "albedo" => albedo = Some(self.lookup_texture(entry)?),
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let albedo = albedo.ok_or_else(|| block.missing("albedo"))?;
+                Arc::new(LambertianMaterial::from_texture(albedo))
+            }
+            "metal" => {
+                let mut albedo = None;
+                let mut fuzz = 0.0;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "albedo" => albedo = Some(self.lookup_texture(entry)?),
+                        "fuzz" => fuzz = entry.number()?,
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let albedo = albedo.ok_or_else(|| block.missing("albedo"))?;
+                Arc::new(MetalMaterial::from_texture(albedo, fuzz))
+            }
+            "dielectric" => {
+                let mut refraction_index = None;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "refraction_index" => refraction_index = Some(entry.positive()?),
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let refraction_index =
+                    refraction_index.ok_or_else(|| block.missing("refraction_index"))?;
+                Arc::new(DielectricMaterial::new(refraction_index))
+            }
+            "diffuse_light" => {
+                let mut emit = None;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "emit" => emit = Some(self.lookup_texture(entry)?),
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let emit = emit.ok_or_else(|| block.missing("emit"))?;
+                Arc::new(DiffuseLight::from_texture(emit))
+            }
+            "isotropic" => {
+                let mut albedo = None;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "albedo" => albedo = Some(self.lookup_texture(entry)?),
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let albedo = albedo.ok_or_else(|| block.missing("albedo"))?;
+                Arc::new(Isotropic::from_texture(albedo))
+            }
+            "henyey_greenstein" => {
+                let mut albedo = None;
+                let mut g = 0.0;
+                for entry in &block.entries {
+                    match entry.key() {
+                        "albedo" => albedo = Some(self.lookup_texture(entry)?),
+                        "g" => {
+                            g = entry.number()?;
+                            if g.abs() >= 1.0 {
+                                return Err(
+                                    entry.values[0].error("`g` must be strictly between -1 and 1")
+                                );
+                            }
+                        }
+                        _ => return Err(entry.unknown(block)),
+                    }
+                }
+                let albedo = albedo.ok_or_else(|| block.missing("albedo"))?;
+                Arc::new(HenyeyGreenstein::from_texture(albedo, g))
+            }
+            _ => {
+                return Err(block.args[1].error(format!("unknown material type `{kind}`")));
+            }
+        };
+        self.materials.insert(name.to_string(), material);
+        Ok(())
+    }
+
+    fn lookup_material(&self, entry: &Entry) -> Result<Arc<dyn Material>, ParseError> {
+        let name = entry.word()?;
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| entry.values[0].error(format!("unknown material `{name}`")))
+    }
+
+    fn sphere(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let mut motion = Motion::default();
+        let mut center = None;
+        let mut center1 = None;
+        let mut radius = None;
+        let mut material = None;
+        let mut density = None;
+        for entry in &block.entries {
+            match entry.key() {
+                "center" => center = Some(entry.vec3()?),
+                "center1" => center1 = Some(entry.vec3()?),
+                "radius" => radius = Some(entry.positive()?),
+                "material" => material = Some(self.lookup_material(entry)?),
+                "density" => density = Some(entry.positive()?),
+                _ => motion.entry(entry, block)?,
+            }
+        }
+        let center = center.ok_or_else(|| block.missing("center"))?;
+        let center1 = center1.unwrap_or(center);
+        let radius = radius.ok_or_else(|| block.missing("radius"))?;
+        let material = material.ok_or_else(|| block.missing("material"))?;
+        let sphere = Sphere::moving(center, center1, radius, material.clone());
+        match density {
+            Some(density) => self.push_medium(sphere, density, material, motion),
+            None => self.push(sphere, motion),
+        }
+        Ok(())
+    }
+
+    fn quad(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let mut motion = Motion::default();
+        let mut origin = None;
+        let mut u = None;
+        let mut v = None;
+        let mut material = None;
+        for entry in &block.entries {
+            match entry.key() {
+                "origin" => origin = Some(entry.vec3()?),
+                "u" => u = Some(entry.vec3()?),
+                "v" => v = Some(entry.vec3()?),
+                "material" => material = Some(self.lookup_material(entry)?),
+                _ => motion.entry(entry, block)?,
+            }
+        }
+        let origin = origin.ok_or_else(|| block.missing("origin"))?;
+        let u = u.ok_or_else(|| block.missing("u"))?;
+        let v = v.ok_or_else(|| block.missing("v"))?;
+        let material = material.ok_or_else(|| block.missing("material"))?;
+        if u.cross(v).is_near_zero() {
+            return Err(block
+                .kind
+                .error("`quad` edges `u` and `v` must not be parallel"));
+        }
+        self.push(Quad::new(origin, u, v, material), motion);
+        Ok(())
+    }
+
+    fn cuboid(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let mut motion = Motion::default();
+        let mut min = None;
+        let mut max = None;
+        let mut material = None;
+        let mut density = None;
+        for entry in &block.entries {
+            match entry.key() {
+                "min" => min = Some(entry.vec3()?),
+                "max" => max = Some(entry.vec3()?),
+                "material" => material = Some(self.lookup_material(entry)?),
+                "density" => density = Some(entry.positive()?),
+                _ => motion.entry(entry, block)?,
+            }
+        }
+        let min = min.ok_or_else(|| block.missing("min"))?;
+        let max = max.ok_or_else(|| block.missing("max"))?;
+        let material = material.ok_or_else(|| block.missing("material"))?;
+        if !(0..3).all(|n| min[n] < max[n]) {
+            return Err(block
+                .kind
+                .error("box must have positive size on every axis"));
+        }
+        let cuboid = make_box(min, max, material.clone());
+        match density {
+            Some(density) => self.push_medium(cuboid, density, material, motion),
+            None => self.push(cuboid, motion),
+        }
+        Ok(())
+    }
+
+    fn plane(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let mut motion = Motion::default();
+        let mut point = None;
+        let mut normal = None;
+        let mut material = None;
+        for entry in &block.entries {
+            match entry.key() {
+                "point" => point = Some(entry.vec3()?),
+                "normal" => normal = Some(entry.direction()?),
+                "material" => material = Some(self.lookup_material(entry)?),
+                _ => motion.entry(entry, block)?,
+            }
+        }
+        let point = point.ok_or_else(|| block.missing("point"))?;
+        let normal = normal.ok_or_else(|| block.missing("normal"))?;
+        let material = material.ok_or_else(|| block.missing("material"))?;
+        self.push(Plane::new(point, normal, material), motion);
+        Ok(())
+    }
+
+    fn disk(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let mut motion = Motion::default();
+        let mut center = None;
+        let mut normal = None;
+        let mut radius = None;
+        let mut material = None;
+        for entry in &block.entries {
+            match entry.key() {
+                "center" => center = Some(entry.vec3()?),
+                "normal" => normal = Some(entry.direction()?),
+                "radius" => radius = Some(entry.positive()?),
+                "material" => material = Some(self.lookup_material(entry)?),
+                _ => motion.entry(entry, block)?,
+            }
+        }
+        let center = center.ok_or_else(|| block.missing("center"))?;
+        let normal = normal.ok_or_else(|| block.missing("normal"))?;
+        let radius = radius.ok_or_else(|| block.missing("radius"))?;
+        let material = material.ok_or_else(|| block.missing("material"))?;
+        self.push(Disk::new(center, normal, radius, material), motion);
+        Ok(())
+    }
+
+    fn mesh(&mut self, block: &Block) -> Result<(), ParseError> {
+        block.args::<0>("")?;
+        let mut motion = Motion::default();
+        let mut file = None;
+        for entry in &block.entries {
+            match entry.key() {
+                "file" => file = Some((entry, entry.string()?)),
+                _ => motion.entry(entry, block)?,
+            }
+        }
+        let (entry, file) = file.ok_or_else(|| block.missing("file"))?;
+        let path = self.dir.join(file);
+        let mesh = match self.meshes.get(&path) {
+            Some(mesh) => mesh.clone(),
+            None => {
+                let meshes = obj::load_obj(&path)
+                    .map_err(|e| entry.values[0].error(format!("failed to load mesh: {e}")))?;
+                let meshes = meshes
+                    .into_iter()
+                    .map(|mesh| Box::new(TriangleMesh::new(mesh.data)) as Box<dyn HitTarget>)
+                    .collect();
+                let mesh: Arc<dyn HitTarget> = Arc::new(Bvh::new(meshes));
+                self.meshes.insert(path, mesh.clone());
+                mesh
+            }
+        };
+        self.push_shared(mesh, motion);
+        Ok(())
+    }
+
+    fn volume(&mut self, block: &Block) -> Result<(), ParseError> {
+        let [kind] = block.args("<type>")?;
+        let mut motion = Motion::default();
+        let mut absorption = 0.0;
+        let mut scattering = 1.0;
+        let mut emission = Vec3::splat(0.0);
+        let mut material = None;
+        let mut file = None;
+        let mut min = None;
+        let mut max = None;
+        let mut scale = 1.0;
+        let mut octaves = 7;
+        let mut seed = 1;
+        for entry in &block.entries {
+            match (kind, entry.key()) {
+                (_, "absorption") => absorption = entry.non_negative()?,
+                (_, "scattering") => scattering = entry.non_negative()?,
+                (_, "emission") => emission = entry.vec3()?,
+                (_, "material") => material = Some(self.lookup_material(entry)?),
+                ("grid", "file") => file = Some((entry, entry.string()?)),
+                ("noise", "min") => min = Some(entry.vec3()?),
+                ("noise", "max") => max = Some(entry.vec3()?),
+                ("noise", "scale") => scale = entry.positive()?,
+                ("noise", "octaves") => octaves = entry.integer()?,
+                ("noise", "seed") => seed = entry.integer()?,
+                _ => motion.entry(entry, block)?,
+            }
+        }
+        let phase = material.unwrap_or_else(|| Arc::new(Isotropic::new(Vec3::splat(1.0))));
+        let medium = match kind {
+            "grid" => {
+                let (entry, file) = file.ok_or_else(|| block.missing("file"))?;
+                let grid = VoxelGrid::load(self.dir.join(file))
+                    .map_err(|e| entry.values[0].error(format!("failed to load grid: {e}")))?;
+                HeterogeneousMedium::new(grid, absorption, scattering, emission, phase)
+            }
+            "noise" => {
+                let min = min.ok_or_else(|| block.missing("min"))?;
+                let max = max.ok_or_else(|| block.missing("max"))?;
+                let bbox = Aabb::from_points(min, max);
+                let density = NoiseDensity::new(Perlin::new(seed), scale, octaves, bbox);
+                HeterogeneousMedium::new(density, absorption, scattering, emission, phase)
+            }
+            _ => {
+                return Err(block.args[0].error(format!(
+                    "unknown volume type `{kind}`, expected `grid` or `noise`"
+                )));
+            }
+        };
+        self.push(medium, motion);
+        Ok(())
+    }
+
+    fn push(&mut self, object: impl HitTarget + 'static, motion: Motion) {
+        if motion.is_identity() {
+            self.world.push(object);
+        } else {
+            self.world.push(motion.instance(Arc::new(object)));
+        }
+    }
+
+    /// Fills the placed `boundary` with a medium. The medium goes outside the
+    /// instance so that its density is measured in world units.
+    fn push_medium(
+        &mut self,
+        boundary: impl HitTarget + 'static,
+        density: f64,
+        phase: Arc<dyn Material>,
+        motion: Motion,
+    ) {
+        if motion.is_identity() {
+            self.world
+                .push(ConstantMedium::new(boundary, density, phase));
+        } else {
+            let boundary = motion.instance(Arc::new(boundary));
+            self.world
+                .push(ConstantMedium::new(boundary, density, phase));
+        }
+    }
+
+    /// Adds an object that may appear several times without copying it.
+    fn push_shared(&mut self, object: Arc<dyn HitTarget>, motion: Motion) {
+        if motion.is_identity() {
+            self.world.push(object);
+        } else {
+            self.world.push(motion.instance(object));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        match parse_scene(source, Path::new("test.scene")) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_blocks() {
+        let scene = parse_scene(
+            "render { width 200; aspect_ratio 16/9; integrator ao; ao_distance 2; roulette_depth 0 }\n\
+             render { seed 0; sampler halton; filter mitchell; filter_radius 1.5 }\n\
+             render { samples 64; min_samples 8; adaptive_threshold 0.01 }\n\
+             camera {\n    lookfrom 1 2 3 # eye\n    vfov 40\n}\n\
+             material red lambertian { albedo 1 0 0 }\n\
+             texture black solid { color 0 0 0 }\n\
+             texture checks checker { scale 2; even 1 1 1; odd black }\n\
+             material floor metal { albedo checks; fuzz 0.1 }\n\
+             sphere { center 0 0 0; radius 1; material red }\n\
+             sphere { center 0 0 0; radius 1; material red; scale 2 1 1; translate 0 0 -5 }\n\
+             sphere { center 0 0 0; center1 0 3 0; radius 1; material red }\n\
+             box { min 0 0 0; max 1 1 1; material red; time 0; time 1; translate 4 0 0 }\n\
+             material fog isotropic { albedo 1 1 1 }\n\
+             box { min 0 0 0; max 1 1 1; material fog; density 0.1 }\n\
+             volume noise { min 0 0 0; max 1 1 1; scattering 2; emission 1 0.5 0; translate 0 -8 0 }\n",
+            Path::new("test.scene"),
+        )
+        .unwrap();
+        assert_eq!(scene.camera.image_width, 200);
+        assert_eq!(scene.camera.aspect_ratio, 16.0 / 9.0);
+        assert_eq!(scene.integrator.kind, IntegratorKind::AmbientOcclusion);
+        assert_eq!(scene.integrator.ao_distance, 2.0);
+        assert_eq!(scene.integrator.roulette_depth, 0);
+        assert_eq!(scene.camera.sampler, SamplerKind::Halton);
+        assert_eq!(scene.camera.filter, FilterKind::Mitchell);
+        assert_eq!(scene.camera.filter_radius, Some(1.5));
+        assert_eq!(scene.camera.samples_per_pixel, 64);
+        assert_eq!(scene.camera.min_samples, 8);
+        assert_eq!(scene.camera.adaptive_threshold, Some(0.01));
+        assert_eq!(scene.camera.lookfrom, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(scene.camera.vfov, 40.0);
+        let bbox = scene.world.bounding_box();
+        assert!((bbox.x.min + 2.0).abs() < 1e-3 && (bbox.z.min + 6.0).abs() < 1e-3);
+        assert!((bbox.x.max - 5.0).abs() < 1e-3 && (bbox.y.max - 4.0).abs() < 1e-3);
+        assert!((bbox.y.min + 8.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reports_locations() {
+        assert_eq!(
+            error("camera {\n  vfov 20 30\n}\n"),
+            "test.scene:2:11: `vfov` takes 1 value, found 2"
+        );
+        assert_eq!(
+            error("sphere { center 0 0 0; radius 1; material red }"),
+            "test.scene:1:43: unknown material `red`"
+        );
+        assert_eq!(
+            error("render { width 0 }"),
+            "test.scene:1:16: `width` must be a positive whole number"
+        );
+        assert_eq!(
+            error("camera {\n  vfov 20\n"),
+            "test.scene:2:10: expected `}`, found end of file"
+        );
+        assert_eq!(
+            error("material m lambertian { albedo wood }"),
+            "test.scene:1:32: unknown texture `wood`"
+        );
+        assert_eq!(
+            error(
+                "material m metal { albedo 1 1 1 }\nbox { min 0 0 0; max 1 1 1; material m; rotate 0 1 0 }"
+            ),
+            "test.scene:2:54: `rotate` takes 4 values, found 3"
+        );
+        assert_eq!(
+            error(
+                "material m metal { albedo 1 1 1 }\nbox { min 0 0 0; max 1 1 1; material m; time 1; time 1 }"
+            ),
+            "test.scene:2:54: keyframe times must increase"
+        );
+        assert_eq!(
+            error("material m henyey_greenstein { albedo 1 1 1; g 1 }"),
+            "test.scene:1:48: `g` must be strictly between -1 and 1"
+        );
+        assert_eq!(
+            error("material m metal { albedo 1 1 1 }\nbox { min 0 0 0; max 1 0 1; material m }"),
+            "test.scene:2:1: box must have positive size on every axis"
+        );
+        assert_eq!(error("light { }"), "test.scene:1:1: unknown block `light`");
+    }
+}
@@ -1,32 +1,49 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 use crate::{
+    aabb::Aabb,
     hit::{HitRecord, HitTarget},
     interval::Interval,
+    light::{Light, SphereLight},
     material::Material,
     ray::Ray,
     vec3::Vec3,
 };
 
 pub struct Sphere {
+    /// Center at time 0.
     center: Vec3,
+    /// Distance the center moves between times 0 and 1.
+    motion: Vec3,
     radius: f64,
     material: Arc<dyn Material>,
 }
 
 impl Sphere {
     pub fn new(center: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        Self::moving(center, center, radius, material)
+    }
+
+    /// Sphere moving in a straight line from `center0` at time 0 to `center1`
+    /// at time 1, and resting at either end outside that range.
+    pub fn moving(center0: Vec3, center1: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
         Self {
-            center,
+            center: center0,
+            motion: center1 - center0,
             radius,
             material,
         }
     }
+
+    fn center_at(&self, time: f64) -> Vec3 {
+        self.center + self.motion * time.clamp(0.0, 1.0)
+    }
 }
 
 impl HitTarget for Sphere {
     fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let oc = self.center - ray.origin;
+        let center = self.center_at(ray.time);
+        let oc = center - ray.origin;
         let a = ray.direction.len_squared();
         let b = ray.direction.dot(oc);
         let c = oc.len_squared() - self.radius * self.radius;
@@ -45,13 +62,39 @@ impl HitTarget for Sphere {
         }
 
         let point = ray.at(t);
-        let outward_normal = (point - self.center) / self.radius;
-        Some(HitRecord::new(
+        let outward_normal = (point - center) / self.radius;
+        let mut hit = HitRecord::new(
             ray,
             point,
             outward_normal,
             t,
+            sphere_uv(outward_normal),
             self.material.clone(),
-        ))
+        );
+        // v spans half a great circle.
+        hit.uv_density = 1.0 / (PI * self.radius);
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::splat(self.radius);
+        let end = self.center + self.motion;
+        Aabb::from_points(self.center - r, self.center + r)
+            .union(Aabb::from_points(end - r, end + r))
     }
+
+    fn lights(&self, lights: &mut Vec<Arc<dyn Light>>) {
+        // Moving lights are still found by scattering.
+        if self.material.is_emissive() && self.motion == Vec3::splat(0.0) {
+            lights.push(Arc::new(SphereLight::new(self.center, self.radius)));
+        }
+    }
+}
+
+/// Spherical coordinates of a point on the unit sphere, with `u` running
+/// around the y axis from -x and `v` from the bottom pole to the top.
+fn sphere_uv(p: Vec3) -> (f64, f64) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
 }
@@ -0,0 +1,428 @@
+//! Spatially varying colors for materials.
+
+use std::{f64::consts::PI, io, path::Path, sync::Arc};
+
+use crate::{hit::HitRecord, image::Image, perlin::Perlin, vec3::Vec3};
+
+pub trait Texture: Send + Sync {
+    /// Color at the surface point described by `hit`.
+    fn value(&self, hit: &HitRecord) -> Vec3;
+}
+
+pub struct SolidColor {
+    albedo: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Vec3) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _hit: &HitRecord) -> Vec3 {
+        self.albedo
+    }
+}
+
+/// Alternates between two textures in a 3D grid of cubes.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    /// Checkers with cubes `scale` units wide.
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+
+    pub fn from_colors(scale: f64, even: Vec3, odd: Vec3) -> Self {
+        Self::new(
+            scale,
+            Arc::new(SolidColor::new(even)),
+            Arc::new(SolidColor::new(odd)),
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, hit: &HitRecord) -> Vec3 {
+        let p = hit.point * self.inv_scale;
+        let sum = p.x.floor() as i64 + p.y.floor() as i64 + p.z.floor() as i64;
+        if sum % 2 == 0 {
+            self.even.value(hit)
+        } else {
+            self.odd.value(hit)
+        }
+    }
+}
+
+/// Smooth grey Perlin noise.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    /// Noise with features about `1 / scale` units across.
+    pub fn new(noise: Perlin, scale: f64) -> Self {
+        Self { noise, scale }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, hit: &HitRecord) -> Vec3 {
+        Vec3::splat(0.5 * (1.0 + self.noise.noise(self.scale * hit.point)))
+    }
+}
+
+/// Grey multi-octave turbulence.
+pub struct TurbulenceTexture {
+    noise: Perlin,
+    scale: f64,
+    octaves: u32,
+}
+
+impl TurbulenceTexture {
+    pub fn new(noise: Perlin, scale: f64, octaves: u32) -> Self {
+        Self {
+            noise,
+            scale,
+            octaves,
+        }
+    }
+}
+
+impl Texture for TurbulenceTexture {
+    fn value(&self, hit: &HitRecord) -> Vec3 {
+        let turbulence = self.noise.turbulence(self.scale * hit.point, self.octaves);
+        Vec3::splat(turbulence.min(1.0))
+    }
+}
+
+/// Veins running across the z axis, distorted by turbulence.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    color: Vec3,
+    octaves: u32,
+}
+
+impl MarbleTexture {
+    pub fn new(noise: Perlin, scale: f64, color: Vec3) -> Self {
+        Self {
+            noise,
+            scale,
+            color,
+            octaves: 7,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, hit: &HitRecord) -> Vec3 {
+        let p = hit.point;
+        let phase = self.scale * p.z + 10.0 * self.noise.turbulence(p, self.octaves);
+        self.color * (0.5 * (1.0 + phase.sin()))
+    }
+}
+
+/// Growth rings around the y axis, wobbled by turbulence.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    light: Vec3,
+    dark: Vec3,
+    octaves: u32,
+}
+
+impl WoodTexture {
+    /// Rings about `1 / scale` units apart.
+    pub fn new(noise: Perlin, scale: f64, light: Vec3, dark: Vec3) -> Self {
+        Self {
+            noise,
+            scale,
+            light,
+            dark,
+            octaves: 4,
+        }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, hit: &HitRecord) -> Vec3 {
+        let p = self.scale * hit.point;
+        let radius = (p.x * p.x + p.z * p.z).sqrt();
+        // Stretch the noise along the trunk so that it reads as grain.
+        let grain = Vec3::new(p.x, 0.2 * p.y, p.z);
+        let rings = radius + 0.3 * self.noise.turbulence(grain, self.octaves);
+        // Sharpen the profile so rings are thin dark lines in light wood.
+        let t = (0.5 * (1.0 + (2.0 * PI * rings).cos())).powi(4);
+        (1.0 - t) * self.light + t * self.dark
+    }
+}
+
+/// How lookups outside of `[0, 1]` are brought back into an image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AddressMode {
+    /// Tile the image.
+    #[default]
+    Wrap,
+    /// Extend the edge texels.
+    Clamp,
+    /// Tile the image, flipping every other copy.
+    Mirror,
+}
+
+impl AddressMode {
+    fn apply(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let i = match self {
+            Self::Wrap => i.rem_euclid(size),
+            Self::Clamp => i.clamp(0, size - 1),
+            Self::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size { i } else { 2 * size - 1 - i }
+            }
+        };
+        i as usize
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Closest texel.
+    Nearest,
+    /// Blend of the four closest texels.
+    Bilinear,
+    /// Bilinear lookups in the two MIP levels closest to the size of the
+    /// ray's footprint, blended together.
+    #[default]
+    Trilinear,
+}
+
+/// Maps an image over the surface's `u, v` coordinates, with `v` running from
+/// the bottom of the image to the top.
+pub struct ImageTexture {
+    /// Full resolution image followed by successively halved copies when
+    /// filtering trilinearly.
+    levels: Vec<MipLevel>,
+    address: AddressMode,
+    filter: TextureFilter,
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+}
+
+impl MipLevel {
+    /// Box filters the level down to half its size in each dimension.
+    fn downsample(&self) -> Self {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = Vec3::splat(0.0);
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (2 * x + dx).min(self.width - 1);
+                    let sy = (2 * y + dy).min(self.height - 1);
+                    sum = sum + self.texels[sy * self.width + sx];
+                }
+                texels.push(sum / 4.0);
+            }
+        }
+        Self {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+impl ImageTexture {
+    /// Takes linear colors, as rendered.
+    pub fn new(image: Image, address: AddressMode, filter: TextureFilter) -> Self {
+        let base = MipLevel {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            texels: image
+                .pixels()
+                .iter()
+                .map(|c| Vec3::new(c.r, c.g, c.b))
+                .collect(),
+        };
+        let mut levels = vec![base];
+        if filter == TextureFilter::Trilinear && !levels[0].texels.is_empty() {
+            while let Some(last) = levels.last()
+                && (last.width > 1 || last.height > 1)
+            {
+                let next = last.downsample();
+                levels.push(next);
+            }
+        }
+        Self {
+            levels,
+            address,
+            filter,
+        }
+    }
+
+    /// Loads an image with [`Image::load`].
+    pub fn load(
+        path: impl AsRef<Path>,
+        address: AddressMode,
+        filter: TextureFilter,
+    ) -> io::Result<Self> {
+        Ok(Self::new(Image::load(path)?, address, filter))
+    }
+
+    fn texel(&self, level: &MipLevel, x: i64, y: i64) -> Vec3 {
+        let x = self.address.apply(x, level.width);
+        let y = self.address.apply(y, level.height);
+        level.texels[y * level.width + x]
+    }
+
+    fn nearest(&self, level: &MipLevel, u: f64, v: f64) -> Vec3 {
+        let x = (u * level.width as f64).floor() as i64;
+        let y = ((1.0 - v) * level.height as f64).floor() as i64;
+        self.texel(level, x, y)
+    }
+
+    fn bilinear(&self, level: &MipLevel, u: f64, v: f64) -> Vec3 {
+        // Texel centers sit at half-integer coordinates.
+        let x = u * level.width as f64 - 0.5;
+        let y = (1.0 - v) * level.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = (1.0 - fx) * self.texel(level, x0, y0) + fx * self.texel(level, x0 + 1, y0);
+        let bottom =
+            (1.0 - fx) * self.texel(level, x0, y0 + 1) + fx * self.texel(level, x0 + 1, y0 + 1);
+        (1.0 - fy) * top + fy * bottom
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, hit: &HitRecord) -> Vec3 {
+        let base = &self.levels[0];
+        if base.texels.is_empty() {
+            return Vec3::new(0.0, 1.0, 1.0);
+        }
+
+        let (u, v) = (hit.u, hit.v);
+        match self.filter {
+            TextureFilter::Nearest => self.nearest(base, u, v),
+            TextureFilter::Bilinear => self.bilinear(base, u, v),
+            TextureFilter::Trilinear => {
+                let texels = hit.uv_footprint() * base.width.max(base.height) as f64;
+                let max_lod = (self.levels.len() - 1) as f64;
+                let lod = if texels > 1.0 {
+                    texels.log2().min(max_lod)
+                } else {
+                    0.0
+                };
+                let fine = lod.floor();
+                let t = lod - fine;
+                let fine_value = self.bilinear(&self.levels[fine as usize], u, v);
+                if t == 0.0 {
+                    return fine_value;
+                }
+                let coarse_value = self.bilinear(&self.levels[fine as usize + 1], u, v);
+                (1.0 - t) * fine_value + t * coarse_value
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::LambertianMaterial, ray::Ray};
+
+    fn hit_at(point: Vec3, (u, v): (f64, f64)) -> HitRecord {
+        let ray = Ray::new(point + Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let material = Arc::new(LambertianMaterial::new(Vec3::splat(0.5)));
+        HitRecord::new(&ray, point, Vec3::new(0.0, 0.0, 1.0), 1.0, (u, v), material)
+    }
+
+    #[test]
+    fn checker_alternates() {
+        let black = Vec3::splat(0.0);
+        let white = Vec3::splat(1.0);
+        let checker = CheckerTexture::from_colors(2.0, white, black);
+        assert_eq!(
+            checker.value(&hit_at(Vec3::new(0.5, 0.5, 0.5), (0.0, 0.0))),
+            white
+        );
+        assert_eq!(
+            checker.value(&hit_at(Vec3::new(2.5, 0.5, 0.5), (0.0, 0.0))),
+            black
+        );
+        assert_eq!(
+            checker.value(&hit_at(Vec3::new(-0.5, 0.5, 0.5), (0.0, 0.0))),
+            black
+        );
+    }
+
+    #[test]
+    fn image_v_points_up() {
+        let mut image = Image::new(1, 2);
+        image.row_mut(0)[0] = Color::new(1.0, 0.0, 0.0);
+        image.row_mut(1)[0] = Color::new(0.0, 0.0, 1.0);
+        let texture = ImageTexture::new(image, AddressMode::Clamp, TextureFilter::Nearest);
+        let origin = Vec3::splat(0.0);
+        assert_eq!(
+            texture.value(&hit_at(origin, (0.5, 0.9))),
+            Vec3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            texture.value(&hit_at(origin, (0.5, 0.1))),
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn addressing() {
+        assert_eq!(
+            [-1, 0, 3, 4].map(|i| AddressMode::Wrap.apply(i, 4)),
+            [3, 0, 3, 0]
+        );
+        assert_eq!(
+            [-1, 0, 3, 4].map(|i| AddressMode::Clamp.apply(i, 4)),
+            [0, 0, 3, 3]
+        );
+        assert_eq!(
+            [-2, -1, 0, 3, 4, 7, 8].map(|i| AddressMode::Mirror.apply(i, 4)),
+            [1, 0, 0, 3, 3, 0, 0]
+        );
+    }
+
+    #[test]
+    fn wide_footprints_average_the_image() {
+        let mut image = Image::new(4, 4);
+        for j in 0..4 {
+            for (i, pixel) in image.row_mut(j).iter_mut().enumerate() {
+                let c = ((i as u32 + j) % 2) as f64;
+                *pixel = Color::new(c, c, c);
+            }
+        }
+        let texture = ImageTexture::new(image, AddressMode::Wrap, TextureFilter::Trilinear);
+        let mut hit = hit_at(Vec3::splat(0.0), (0.3, 0.6));
+        hit.uv_density = 1.0;
+
+        hit.footprint = 0.0;
+        let sharp = texture.value(&hit);
+        assert!(sharp.x > 0.0 && sharp.x < 1.0);
+        hit.footprint = 10.0;
+        assert_eq!(texture.value(&hit), Vec3::splat(0.5));
+    }
+}
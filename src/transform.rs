@@ -0,0 +1,518 @@
+//! Affine transforms of points, directions and normals.
+
+use crate::{aabb::Aabb, vec3::Vec3};
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// 4x4 affine transform, stored together with its inverse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        matrix: IDENTITY,
+        inverse: IDENTITY,
+    };
+
+    /// Transform from the upper three rows of an affine matrix, or `None` if
+    /// it can't be inverted.
+    pub fn from_rows(rows: [[f64; 4]; 3]) -> Option<Self> {
+        let matrix = [rows[0], rows[1], rows[2], [0.0, 0.0, 0.0, 1.0]];
+        let inverse = invert_affine(&matrix)?;
+        Some(Self { matrix, inverse })
+    }
+
+    pub fn translate(offset: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for n in 0..3 {
+            matrix[n][3] = offset[n];
+            inverse[n][3] = -offset[n];
+        }
+        Self { matrix, inverse }
+    }
+
+    /// # Panics
+    ///
+    /// If any of the factors is zero.
+    pub fn scale(factors: Vec3) -> Self {
+        assert!(
+            factors.x != 0.0 && factors.y != 0.0 && factors.z != 0.0,
+            "scale factors must be non-zero"
+        );
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for n in 0..3 {
+            matrix[n][n] = factors[n];
+            inverse[n][n] = 1.0 / factors[n];
+        }
+        Self { matrix, inverse }
+    }
+
+    /// Counterclockwise rotation by `degrees` when looking down `axis`
+    /// towards the origin.
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        let Vec3 { x, y, z } = axis.unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos;
+        let matrix = [
+            [
+                cos + x * x * k,
+                x * y * k - z * sin,
+                x * z * k + y * sin,
+                0.0,
+            ],
+            [
+                y * x * k + z * sin,
+                cos + y * y * k,
+                y * z * k - x * sin,
+                0.0,
+            ],
+            [
+                z * x * k - y * sin,
+                z * y * k + x * sin,
+                cos + z * z * k,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        Self {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+
+    /// Applies `self` and then `next`.
+    pub fn then(self, next: Self) -> Self {
+        Self {
+            matrix: multiply(&next.matrix, &self.matrix),
+            inverse: multiply(&self.inverse, &next.inverse),
+        }
+    }
+
+    /// Splits the transform into a stretch, a rotation and a translation,
+    /// for blending between keyframes.
+    pub fn decompose(&self) -> Decomposed {
+        let mut linear = self.matrix;
+        for row in linear.iter_mut().take(3) {
+            row[3] = 0.0;
+        }
+        // Averaging with the inverse transpose converges on the nearest
+        // rotation, possibly with a reflection.
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let Some(inverse) = invert_affine(&rotation) else {
+                break;
+            };
+            let next: Matrix = std::array::from_fn(|i| {
+                std::array::from_fn(|j| 0.5 * (rotation[i][j] + inverse[j][i]))
+            });
+            let change = (0..3)
+                .flat_map(|i| (0..3).map(move |j| (i, j)))
+                .map(|(i, j)| (next[i][j] - rotation[i][j]).abs())
+                .fold(0.0, f64::max);
+            rotation = next;
+            if change < 1e-12 {
+                break;
+            }
+        }
+        // Any reflection goes in the stretch, which is blended linearly.
+        if determinant3(&rotation) < 0.0 {
+            for row in rotation.iter_mut().take(3) {
+                row.iter_mut().take(3).for_each(|value| *value = -*value);
+            }
+        }
+        let m = &self.matrix;
+        Decomposed {
+            translation: Vec3::new(m[0][3], m[1][3], m[2][3]),
+            rotation: Quaternion::from_matrix(&rotation),
+            stretch: multiply(&transpose(&rotation), &linear),
+        }
+    }
+
+    pub fn inverse(self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Vec3) -> Vec3 {
+        let m = &self.matrix;
+        Vec3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        apply_linear(&self.matrix, v)
+    }
+
+    /// Transforms a surface normal with the inverse transpose, keeping it
+    /// perpendicular to transformed tangents. The result isn't normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+
+    /// Box around the transformed corners of `bbox`.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        if !bbox.is_bounded() {
+            return Aabb::UNIVERSE;
+        }
+        let (min, max) = (bbox.min(), bbox.max());
+        (0..8).fold(Aabb::EMPTY, |acc, corner| {
+            let pick = |bit: usize, n: usize| if corner & bit == 0 { min[n] } else { max[n] };
+            let p = self.point(Vec3::new(pick(1, 0), pick(2, 1), pick(4, 2)));
+            acc.union(Aabb::from_points(p, p))
+        })
+    }
+
+    /// Factor by which the transform scales lengths on average.
+    pub fn scale_factor(&self) -> f64 {
+        determinant3(&self.matrix).abs().cbrt()
+    }
+
+    /// Whether the transform only rotates, reflects, translates and scales
+    /// uniformly, and so preserves angles.
+    pub fn is_similarity(&self) -> bool {
+        let m = &self.matrix;
+        let column = |c: usize| Vec3::new(m[0][c], m[1][c], m[2][c]);
+        let [a, b, c] = [column(0), column(1), column(2)];
+        let scale = a.len_squared();
+        let close = |x: f64, y: f64| (x - y).abs() <= 1e-9 * scale;
+        close(b.len_squared(), scale)
+            && close(c.len_squared(), scale)
+            && close(a.dot(b), 0.0)
+            && close(b.dot(c), 0.0)
+            && close(c.dot(a), 0.0)
+    }
+}
+
+/// Transform split into a stretch, then a rotation, then a translation.
+/// Blending the parts separately keeps rotating keyframes from shrinking
+/// and shearing in between.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decomposed {
+    translation: Vec3,
+    rotation: Quaternion,
+    /// Scaling, shearing and any reflection, without a translation.
+    stretch: Matrix,
+}
+
+impl Decomposed {
+    /// Transform `t` of the way from `self` to `other`, turning the short
+    /// way round, or `None` where the stretch collapses.
+    pub fn blend(&self, other: &Self, t: f64) -> Option<Transform> {
+        let rotation = self.rotation.slerp(other.rotation, t).matrix();
+        let stretch = std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                self.stretch[i][j] + t * (other.stretch[i][j] - self.stretch[i][j])
+            })
+        });
+        let mut matrix = multiply(&rotation, &stretch);
+        let translation = self.translation + t * (other.translation - self.translation);
+        for (n, row) in matrix.iter_mut().take(3).enumerate() {
+            row[3] = translation[n];
+        }
+        Transform::from_rows([matrix[0], matrix[1], matrix[2]])
+    }
+
+    /// Box around `bbox` at every blend from `self` to `other`.
+    pub fn motion_bounds(&self, other: &Self, bbox: &Aabb) -> Aabb {
+        const STEPS: u32 = 16;
+        if !bbox.is_bounded() {
+            return Aabb::UNIVERSE;
+        }
+        let (min, max) = (bbox.min(), bbox.max());
+        let corners = (0..8).map(|corner| {
+            let pick = |bit: usize, n: usize| if corner & bit == 0 { min[n] } else { max[n] };
+            Vec3::new(pick(1, 0), pick(2, 1), pick(4, 2))
+        });
+        let change: Matrix = std::array::from_fn(|i| {
+            std::array::from_fn(|j| other.stretch[i][j] - self.stretch[i][j])
+        });
+        // Fastest any corner moves over the blend, from the translation, the
+        // turn at its furthest reach from the origin, and the stretch.
+        let reach = corners
+            .clone()
+            .map(|p| {
+                apply_linear(&self.stretch, p)
+                    .len()
+                    .max(apply_linear(&other.stretch, p).len())
+            })
+            .fold(0.0, f64::max);
+        let stretching = corners
+            .map(|p| apply_linear(&change, p).len())
+            .fold(0.0, f64::max);
+        let angle = self.rotation.angle(other.rotation);
+        let speed = (other.translation - self.translation).len() + angle * reach + stretching;
+        // Without a turn the corners move in straight lines, which the
+        // samples cover. Otherwise they stray between samples by at most half
+        // a step's travel.
+        let padding = if angle < 1e-6 {
+            0.0
+        } else {
+            speed / STEPS as f64
+        };
+        let sampled = (0..=STEPS).fold(Aabb::EMPTY, |acc, step| {
+            match self.blend(other, step as f64 / STEPS as f64) {
+                Some(transform) => acc.union(transform.bounding_box(bbox)),
+                None => acc,
+            }
+        });
+        Aabb::new(
+            sampled.x.expand(padding),
+            sampled.y.expand(padding),
+            sampled.z.expand(padding),
+        )
+    }
+}
+
+/// Unit quaternion for a rotation, stored as (w, x, y, z).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Quaternion([f64; 4]);
+
+impl Quaternion {
+    fn from_matrix(m: &Matrix) -> Self {
+        // Dividing by the largest of the four components keeps the others
+        // accurate.
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            [
+                s / 4.0,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            ]
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            [
+                (m[2][1] - m[1][2]) / s,
+                s / 4.0,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            ]
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            [
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.0,
+                (m[1][2] + m[2][1]) / s,
+            ]
+        } else {
+            let s = 2.0 * (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            [
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.0,
+            ]
+        };
+        Self(q).normalized()
+    }
+
+    fn matrix(&self) -> Matrix {
+        let [w, x, y, z] = self.0;
+        [
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
+    fn dot(&self, other: Self) -> f64 {
+        (0..4).map(|n| self.0[n] * other.0[n]).sum()
+    }
+
+    fn normalized(self) -> Self {
+        let len = self.dot(self).sqrt();
+        Self(self.0.map(|c| c / len))
+    }
+
+    /// Angle of the shortest turn between the two rotations, in radians.
+    fn angle(&self, other: Self) -> f64 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    /// Rotation `t` of the way from `self` to `other` at a steady rate, the
+    /// short way round.
+    fn slerp(&self, other: Self, t: f64) -> Self {
+        let mut dot = self.dot(other);
+        let mut other = other.0;
+        // `q` and `-q` are the same rotation; picking the nearer one takes
+        // the shorter turn.
+        if dot < 0.0 {
+            dot = -dot;
+            other = other.map(|c| -c);
+        }
+        let (a, b) = if dot > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let angle = dot.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Self(std::array::from_fn(|n| a * self.0[n] + b * other[n])).normalized()
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0.0; 4]; 4];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+/// Applies the linear part of `m` to `v`.
+fn apply_linear(m: &Matrix, v: Vec3) -> Vec3 {
+    Vec3::new(
+        m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+        m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+        m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+    )
+}
+
+fn transpose(m: &Matrix) -> Matrix {
+    std::array::from_fn(|i| std::array::from_fn(|j| m[j][i]))
+}
+
+fn determinant3(m: &Matrix) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/// Inverts the linear part through its adjugate and undoes the translation.
+fn invert_affine(m: &Matrix) -> Option<Matrix> {
+    let det = determinant3(m);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let mut inverse = IDENTITY;
+    for (i, row) in inverse.iter_mut().take(3).enumerate() {
+        for (j, value) in row.iter_mut().take(3).enumerate() {
+            *value = cofactor(j, i) / det;
+        }
+        row[3] = -(0..3).map(|k| row[k] * m[k][3]).sum::<f64>();
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 1e-9, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn composes_and_inverts() {
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 0.5))
+            .then(Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 90.0))
+            .then(Transform::translate(Vec3::new(1.0, 2.0, 3.0)));
+        let p = Vec3::new(1.0, 1.0, 1.0);
+        // (2, 1, 0.5) rotated a quarter turn about y is (0.5, 1, -2).
+        assert_close(transform.point(p), Vec3::new(1.5, 3.0, 1.0));
+        assert_close(transform.inverse().point(transform.point(p)), p);
+
+        let general = Transform::from_rows([
+            transform.matrix[0],
+            transform.matrix[1],
+            transform.matrix[2],
+        ])
+        .unwrap();
+        assert_close(general.inverse().point(transform.point(p)), p);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let transform = Transform::rotate(Vec3::new(1.0, 1.0, 0.0), 30.0)
+            .then(Transform::scale(Vec3::new(3.0, 1.0, 0.2)));
+        let tangent = Vec3::new(1.0, -1.0, 0.0);
+        let normal = Vec3::new(1.0, 1.0, 0.0);
+        let dot = transform.vector(tangent).dot(transform.normal(normal));
+        assert!(dot.abs() < 1e-9);
+        assert!(!transform.is_similarity());
+        assert!(Transform::rotate(normal, 30.0).is_similarity());
+    }
+
+    #[test]
+    fn blends_keyframes() {
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let a = Transform::translate(Vec3::new(1.0, 0.0, 0.0)).decompose();
+        let b = Transform::scale(Vec3::new(1.0, 3.0, 1.0))
+            .then(Transform::rotate(z, 90.0))
+            .decompose();
+        let p = Vec3::new(1.0, 0.0, 0.0);
+        // Turning keeps lengths instead of cutting the corner.
+        let half = a.blend(&b, 0.5).unwrap();
+        let turned = 0.5f64.sqrt();
+        assert_close(half.point(p), Vec3::new(turned + 0.5, turned, 0.0));
+        assert_close(half.inverse().point(half.point(p)), p);
+        assert_close(a.blend(&b, 0.0).unwrap().point(p), Vec3::new(2.0, 0.0, 0.0));
+        assert_close(
+            b.blend(&a, 0.0).unwrap().point(z + p),
+            Vec3::new(0.0, 1.0, 1.0),
+        );
+
+        // Half a turn apart, the blend still turns rather than collapsing.
+        let c = Transform::rotate(z, 90.0).decompose();
+        let d = Transform::rotate(z, 270.0).decompose();
+        let half = c.blend(&d, 0.5).unwrap();
+        let q = half.point(p);
+        assert!((q.x.abs() - 1.0).abs() < 1e-9 && q.y.abs() < 1e-9);
+        let bounds = c.motion_bounds(&d, &Aabb::from_points(p, p));
+        assert!(bounds.y.min <= -1.0 && bounds.y.max >= 1.0);
+        assert!(bounds.x.min <= q.x && q.x <= bounds.x.max);
+    }
+}
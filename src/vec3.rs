@@ -1,6 +1,9 @@
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::{
+    f64::consts::PI,
+    ops::{Add, Div, Index, Mul, Neg, Sub},
+};
 
-use rand::{Rng, rng};
+use crate::sampler::Sampler;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Vec3 {
@@ -18,32 +21,25 @@ impl Vec3 {
         Self::new(n, n, n)
     }
 
-    pub fn random() -> Self {
-        let mut rng = rng();
-        Self::new(rng.random(), rng.random(), rng.random())
+    pub fn random(sampler: &mut dyn Sampler) -> Self {
+        Self::new(sampler.get_1d(), sampler.get_1d(), sampler.get_1d())
     }
 
-    pub fn random_range(min: f64, max: f64) -> Self {
-        let mut rng = rng();
-        Self::new(
-            rng.random_range(min..max),
-            rng.random_range(min..max),
-            rng.random_range(min..max),
-        )
+    pub fn random_range(sampler: &mut dyn Sampler, min: f64, max: f64) -> Self {
+        Self::random(sampler) * (max - min) + min
     }
 
-    pub fn random_unit() -> Self {
-        loop {
-            let p = Self::random_range(-1.0, 1.0);
-            let len_sqrd = p.len_squared();
-            if len_sqrd > f64::EPSILON && len_sqrd <= 1.0 {
-                break p / len_sqrd.sqrt();
-            }
-        }
+    /// Uniformly distributed direction, mapped from a 2D sample.
+    pub fn random_unit(sampler: &mut dyn Sampler) -> Self {
+        let (u, v) = sampler.get_2d();
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let (sin, cos) = (2.0 * PI * v).sin_cos();
+        Self::new(r * cos, r * sin, z)
     }
 
-    pub fn random_unit_on_hemisphere(normal: Vec3) -> Self {
-        let u = Self::random_unit();
+    pub fn random_unit_on_hemisphere(sampler: &mut dyn Sampler, normal: Vec3) -> Self {
+        let u = Self::random_unit(sampler);
         if u.dot(normal) > 0.0 {
             // Same hemisphere
             u
@@ -52,18 +48,12 @@ impl Vec3 {
         }
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        let mut rng = rng();
-        loop {
-            let p = Vec3::new(
-                rng.random_range(-1.0..1.0),
-                rng.random_range(-1.0..1.0),
-                0.0,
-            );
-            if p.len_squared() < 1.0 {
-                return p;
-            }
-        }
+    /// Uniformly distributed point in the unit disk on the xy plane.
+    pub fn random_in_unit_disk(sampler: &mut dyn Sampler) -> Self {
+        let (u, v) = sampler.get_2d();
+        let r = u.sqrt();
+        let (sin, cos) = (2.0 * PI * v).sin_cos();
+        Vec3::new(r * cos, r * sin, 0.0)
     }
 
     pub fn dot(self, rhs: Self) -> f64 {
@@ -91,6 +81,10 @@ impl Vec3 {
         x * x + y * y + z * z
     }
 
+    pub fn max_component(&self) -> f64 {
+        self.x.max(self.y).max(self.z)
+    }
+
     pub fn is_near_zero(&self) -> bool {
         let epsilon = 1e-8;
         self.x.abs() < epsilon && self.y.abs() < epsilon && self.z.abs() < epsilon
@@ -188,6 +182,19 @@ impl Div<f64> for Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {index}"),
+        }
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Self;
 